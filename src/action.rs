@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use crate::difficulty::Difficulty;
+use crate::item::Slot;
+use crate::map::Direction;
+
+/// Everything the player can do to the world. Frontends translate their
+/// input into these and feed them to `World::update`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    Move(Direction),
+    Interact,
+    /// Drops one of the inventory item at the given index onto the
+    /// player's tile.
+    Drop(usize),
+    /// Uses the consumable at the given index in the inventory.
+    Use(usize),
+    /// Equips the inventory item at the given index.
+    Equip(usize),
+    Unequip(Slot),
+    SetDifficulty(Difficulty),
+    Quit,
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::entity::{Ai, ENEMY_MOVE_CHANCE};
+use crate::item::Item;
+use crate::map::TileKind;
+use crate::status::Status;
+
+/// A kind of enemy, as defined in the world's enemy catalog.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Archetype {
+    pub id: String,
+    pub name: String,
+    pub sprite: char,
+    pub color: u8,
+    /// The range (inclusive) health is picked from, before difficulty.
+    pub health: (u32, u32),
+    /// The range (inclusive) damage is picked from, before difficulty.
+    pub damage: (u32, u32),
+    /// How likely the enemy is to spawn on each kind of tile. Tiles
+    /// that aren't listed never spawn it.
+    pub spawn: HashMap<TileKind, f32>,
+    #[serde(default)]
+    pub ai: Ai,
+    /// The chance the enemy gets to move each turn.
+    #[serde(default = "default_move_chance")]
+    pub move_chance: f32,
+    /// How many spawn together (inclusive).
+    #[serde(default = "default_pack")]
+    pub pack: (u32, u32),
+    #[serde(default)]
+    pub loot: Vec<Drop>,
+    /// Given to the player whenever the enemy lands a hit.
+    #[serde(default)]
+    pub inflicts: Option<Status>,
+}
+
+fn default_move_chance() -> f32 {
+    ENEMY_MOVE_CHANCE
+}
+
+fn default_pack() -> (u32, u32) {
+    (1, 1)
+}
+
+/// One entry in an enemy's loot table. Each entry is rolled on its
+/// own, so an enemy can drop several things at once.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Drop {
+    /// The chance of dropping, before the difficulty's `loot_mul`.
+    pub chance: f32,
+    pub loot: Loot,
+}
+
+/// Something left on the ground by a dead enemy.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum Loot {
+    Food(u32),
+    Item(Item),
+}
+
+impl Loot {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Food(_) => "food",
+            Self::Item(item) => &item.name,
+        }
+    }
+}
+
+/// Every kind of enemy in the world, in the order they were defined.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bestiary {
+    archetypes: Vec<Archetype>,
+}
+
+impl Bestiary {
+    pub fn new(archetypes: Vec<Archetype>) -> Self {
+        Self { archetypes }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Archetype> {
+        self.archetypes.iter().find(|a| a.id == id)
+    }
+
+    pub fn archetypes(&self) -> &[Archetype] {
+        &self.archetypes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.archetypes.is_empty()
+    }
+}
+
+impl Archetype {
+    pub fn spawn_percentage(&self, tile: TileKind) -> f32 {
+        self.spawn.get(&tile).copied().unwrap_or(0.0)
+    }
+}
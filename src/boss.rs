@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+
+use crate::entity::{Ai, Behaviour, Entity, EntityKind};
+use crate::input::TurnResult;
+use crate::map::{Direction, HEIGHT, WIDTH};
+use crate::path;
+use crate::render;
+use crate::world::World;
+use crate::{bad, good};
+
+/// A stage of a boss fight, starting once the boss's health drops low
+/// enough.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Phase {
+    /// The phase starts once the boss's health drops to this or below.
+    pub below: u32,
+    /// Replaces the boss's damage for the rest of the fight.
+    #[serde(default)]
+    pub damage: Option<u32>,
+    #[serde(default)]
+    pub attack: Option<Attack>,
+    /// Turns to wait between special attacks.
+    #[serde(default = "default_cooldown")]
+    pub cooldown: u32,
+    /// Minions summoned when the phase starts.
+    #[serde(default)]
+    pub summon: Option<Summon>,
+}
+
+fn default_cooldown() -> u32 {
+    3
+}
+
+/// A special attack. It's telegraphed for a turn before it lands, so the
+/// player has a chance to get out of the way.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Attack {
+    /// Hits a line of tiles from the boss towards the player.
+    Line { length: u32, damage: u32 },
+    /// Hits every tile within `radius` of the boss.
+    Burst { radius: u32, damage: u32 },
+}
+
+impl Attack {
+    fn reach(&self) -> u32 {
+        match self {
+            Self::Line { length, .. } => *length,
+            Self::Burst { radius, .. } => *radius,
+        }
+    }
+
+    fn damage(&self) -> u32 {
+        match self {
+            Self::Line { damage, .. } | Self::Burst { damage, .. } => *damage,
+        }
+    }
+
+    /// The tiles the attack will hit.
+    fn tiles(&self, (x, y): (u32, u32), target: (u32, u32)) -> Vec<(u32, u32)> {
+        let in_bounds = |(x, y): &(u32, u32)| (*x as usize) < WIDTH && (*y as usize) < HEIGHT;
+
+        match self {
+            Self::Line { length, .. } => {
+                let dx = target.0 as i32 - x as i32;
+                let dy = target.1 as i32 - y as i32;
+                let Some(dir) = Direction::from_diff(dx, dy) else {
+                    return Vec::new();
+                };
+
+                let (dx, dy) = dir.diff();
+                (1..=*length as i32)
+                    .map_while(|i| {
+                        Some((x.checked_add_signed(dx * i)?, y.checked_add_signed(dy * i)?))
+                    })
+                    .filter(in_bounds)
+                    .collect()
+            }
+            Self::Burst { radius, .. } => {
+                let r = *radius as i32;
+                (-r..=r)
+                    .flat_map(|dy| (-r..=r).map(move |dx| (dx, dy)))
+                    .filter(|d| *d != (0, 0))
+                    .filter_map(|(dx, dy)| {
+                        Some((x.checked_add_signed(dx)?, y.checked_add_signed(dy)?))
+                    })
+                    .filter(in_bounds)
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Minions a boss calls in. They spawn next to the boss and chase the
+/// player.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Summon {
+    pub count: u32,
+    pub health: u32,
+    pub damage: u32,
+}
+
+/// How far along its fight a boss is.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BossState {
+    /// The health the boss started with, for the health bar. Filled in
+    /// when the world is loaded.
+    pub max_health: u32,
+    /// How many phases have started.
+    pub phase: usize,
+    pub cooldown: u32,
+    /// The tiles the boss's next attack will hit, and how hard.
+    pub telegraph: Vec<(u32, u32)>,
+    pub telegraph_damage: u32,
+}
+
+/// Lets the boss at `(x, y)` take its turn: moving on to its next phase,
+/// landing a telegraphed attack, or winding up a new one.
+pub fn act(
+    (x, y): (u32, u32),
+    health: u32,
+    damage: &mut u32,
+    phases: &[Phase],
+    state: &mut BossState,
+    world: &mut World,
+) -> TurnResult {
+    if let Some(next) = phases.get(state.phase) {
+        if health <= next.below {
+            state.phase += 1;
+            state.cooldown = next.cooldown;
+            state.telegraph.clear();
+            if let Some(d) = next.damage {
+                *damage = d;
+            }
+            if let Some(summon) = &next.summon {
+                summon_minions((x, y), summon, world);
+            }
+
+            return good!(BossPhase);
+        }
+    }
+
+    if !state.telegraph.is_empty() {
+        let tiles = std::mem::take(&mut state.telegraph);
+        let player = &mut world.player;
+        if !tiles.contains(&(player.x, player.y)) {
+            return good!(BossMissed);
+        }
+
+        let damage = player.mitigate(state.telegraph_damage);
+        if damage >= player.health {
+            player.health = 0;
+            return bad!(ViolentDeath);
+        }
+
+        player.health -= damage;
+        return good!(BossHit, damage);
+    }
+
+    let Some(phase) = state.phase.checked_sub(1).and_then(|i| phases.get(i)) else {
+        return good!();
+    };
+    let Some(attack) = &phase.attack else {
+        return good!();
+    };
+
+    if state.cooldown > 0 {
+        state.cooldown -= 1;
+        return good!();
+    }
+
+    let player = (world.player.x, world.player.y);
+    if player.0.abs_diff(x).max(player.1.abs_diff(y)) > attack.reach() {
+        return good!();
+    }
+
+    state.telegraph = attack.tiles((x, y), player);
+    state.telegraph_damage = attack.damage();
+    state.cooldown = phase.cooldown;
+    good!(BossTelegraph)
+}
+
+fn summon_minions(pos: (u32, u32), summon: &Summon, world: &mut World) {
+    let free: Vec<_> = path::neighbors(&world.map, pos.0, pos.1, true)
+        .filter(|&(x, y)| (x, y) != (world.player.x, world.player.y))
+        .filter(|&(x, y)| !world.entities.iter().any(|e| (e.x, e.y) == (x, y)))
+        .take(summon.count as usize)
+        .collect();
+
+    for (x, y) in free {
+        let kind = EntityKind::Enemy {
+            health: summon.health,
+            damage: summon.damage,
+            ai: Ai {
+                behaviour: Behaviour::Chase,
+                perception: (WIDTH + HEIGHT) as u32,
+                flee_below: 0,
+            },
+            archetype: None,
+        };
+        world.entities.push(Entity::new(x, y, kind, false));
+    }
+}
+
+/// Draws a health bar for the boss, `width` characters wide.
+pub fn draw_health(health: u32, state: &BossState, width: u32) {
+    let max = state.max_health.max(health).max(1);
+    let filled = (health * width).div_ceil(max);
+
+    render::fg(136);
+    render::print("Boss: [");
+    render::fg(1);
+    render::print("=".repeat(filled as usize));
+    render::fg(8);
+    render::print("-".repeat((width - filled) as usize));
+    render::fg(136);
+    render::print(format!("] {health}/{max}"));
+    if state.phase > 0 {
+        render::print(format!("  Phase {}", state.phase + 1));
+    }
+}
@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const USAGE: &str = "\
+Usage: frob-adventure [OPTIONS]
+
+Options:
+    --world <PATH>    Load the world manifest at PATH instead of the built-in world
+    --seed <SEED>     Start a run from the given seed
+    --daily           Start today's daily challenge (everyone gets the same seed)
+    --record <PATH>   Write the run's replay to PATH instead of the data directory
+    --replay <PATH>   Watch the replay at PATH instead of playing
+    --speed <MS>      Milliseconds between replayed turns; 0 steps on keypress [default: 200]
+    --theme <THEME>   Draw with a built-in theme (ascii, unicode, cp437, emoji) or the theme file at THEME
+    -h, --help        Print this message";
+
+const DEFAULT_SPEED: u64 = 200;
+
+#[derive(Debug, Clone)]
+pub struct Args {
+    pub world: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub daily: bool,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub speed: u64,
+    pub theme: Option<String>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            world: None,
+            seed: None,
+            daily: false,
+            record: None,
+            replay: None,
+            speed: DEFAULT_SPEED,
+            theme: None,
+        }
+    }
+}
+
+impl Args {
+    /// Parses the process arguments. On failure, returns the
+    /// message to print before exiting.
+    pub fn parse() -> Result<Self, String> {
+        let mut args = Self::default();
+        let mut iter = std::env::args().skip(1);
+
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--world" => {
+                    let path = iter.next().ok_or("--world expects a path")?;
+                    args.world = Some(PathBuf::from(path));
+                }
+                "--seed" => {
+                    let seed = iter.next().ok_or("--seed expects a number")?;
+                    let seed = seed.parse().map_err(|_| format!("invalid seed `{seed}`"))?;
+                    args.seed = Some(seed);
+                }
+                "--daily" => args.daily = true,
+                "--record" => {
+                    let path = iter.next().ok_or("--record expects a path")?;
+                    args.record = Some(PathBuf::from(path));
+                }
+                "--replay" => {
+                    let path = iter.next().ok_or("--replay expects a path")?;
+                    args.replay = Some(PathBuf::from(path));
+                }
+                "--speed" => {
+                    let speed = iter.next().ok_or("--speed expects a number")?;
+                    args.speed = speed
+                        .parse()
+                        .map_err(|_| format!("invalid speed `{speed}`"))?;
+                }
+                "--theme" => {
+                    let theme = iter.next().ok_or("--theme expects a name or path")?;
+                    args.theme = Some(theme);
+                }
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                _ => return Err(format!("unknown argument `{arg}`\n\n{USAGE}")),
+            }
+        }
+
+        Ok(args)
+    }
+
+    /// The seed to start the run with: an explicit `--seed`, today's
+    /// daily seed, or a random one.
+    pub fn seed(&self) -> u64 {
+        if let Some(seed) = self.seed {
+            seed
+        } else if self.daily {
+            daily_seed()
+        } else {
+            rand::random()
+        }
+    }
+}
+
+/// The number of whole days since the Unix epoch (UTC).
+pub fn daily_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / (60 * 60 * 24))
+        .unwrap_or(0)
+}
@@ -0,0 +1,83 @@
+use std::fmt::{self, Display};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Autosave every this many turns; 0 turns it off.
+    pub autosave_interval: u32,
+    /// How many older copies of each save slot to keep.
+    pub backups: u32,
+    /// Use colors that don't rely on telling red and green apart.
+    pub colorblind: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            autosave_interval: 25,
+            backups: 3,
+            colorblind: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, serde_json::Error),
+    Invalid(PathBuf, String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "failed to access `{}`: {e}", path.display()),
+            Self::Parse(path, e) => write!(f, "invalid config `{}`: {e}", path.display()),
+            Self::Invalid(path, e) => write!(f, "invalid config `{}`: {e}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Settings {
+    /// Loads the settings file, creating it with the defaults if it
+    /// doesn't exist yet.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = Self::path();
+        match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| ConfigError::Parse(path, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let settings = Self::default();
+                settings.save()?;
+                Ok(settings)
+            }
+            Err(e) => Err(ConfigError::Io(path, e)),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let path = Self::path();
+        let json = serde_json::to_string_pretty(self).expect("Failed to serialize settings");
+        fs::write(&path, json).map_err(|e| ConfigError::Io(path, e))
+    }
+
+    fn path() -> PathBuf {
+        let mut path = config_dir();
+        path.push("settings.json");
+        path
+    }
+}
+
+/// The directory user configuration is kept in.
+pub fn config_dir() -> PathBuf {
+    let mut dir = dirs::config_dir().unwrap_or_else(|| PathBuf::from("./"));
+    dir.push("frob-adventure");
+    fs::create_dir_all(&dir).expect("Failed to create config directory");
+    dir
+}
@@ -1,6 +1,8 @@
 use std::ops::Mul;
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Difficulty {
     pub food_mul: f32,
     pub food_food_mul: f32,
@@ -8,6 +10,14 @@ pub struct Difficulty {
     pub enemy_mul: f32,
     pub enemy_health_mul: f32,
     pub enemy_damage_mul: f32,
+
+    /// Scales the chance of enemies dropping loot.
+    #[serde(default = "one")]
+    pub loot_mul: f32,
+}
+
+fn one() -> f32 {
+    1.0
 }
 
 impl Difficulty {
@@ -33,6 +43,11 @@ impl Difficulty {
         self
     }
 
+    pub const fn loot_mul(mut self, loot_mul: f32) -> Self {
+        self.loot_mul = loot_mul;
+        self
+    }
+
     pub const fn none() -> Self {
         Self {
             food_mul: -10.0,
@@ -41,6 +56,8 @@ impl Difficulty {
             enemy_mul: -10.0,
             enemy_health_mul: -10.0,
             enemy_damage_mul: -10.0,
+
+            loot_mul: -10.0,
         }
     }
 
@@ -51,6 +68,7 @@ impl Difficulty {
             .enemy_mul(0.75)
             .enemy_health_mul(0.8)
             .enemy_damage_mul(0.75)
+            .loot_mul(1.25)
     }
 
     pub const fn normal() -> Self {
@@ -64,6 +82,30 @@ impl Difficulty {
             .enemy_mul(1.5)
             .enemy_health_mul(1.5)
             .enemy_damage_mul(2.0)
+            .loot_mul(0.75)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "none" => Self::none(),
+            "easy" => Self::easy(),
+            "normal" => Self::normal(),
+            "hard" => Self::hard(),
+            _ => None?,
+        })
+    }
+
+    /// The name of the preset this matches, if any.
+    pub fn name(&self) -> &'static str {
+        if *self == Self::easy() {
+            "easy"
+        } else if *self == Self::normal() {
+            "normal"
+        } else if *self == Self::hard() {
+            "hard"
+        } else {
+            "custom"
+        }
     }
 
     pub const fn new() -> Self {
@@ -74,6 +116,8 @@ impl Difficulty {
             enemy_mul: 1.0,
             enemy_health_mul: 1.0,
             enemy_damage_mul: 1.0,
+
+            loot_mul: 1.0,
         }
     }
 }
@@ -94,6 +138,8 @@ impl Mul for Difficulty {
             enemy_mul: self.enemy_mul * other.enemy_mul,
             enemy_health_mul: self.enemy_health_mul * other.enemy_health_mul,
             enemy_damage_mul: self.enemy_damage_mul * other.enemy_damage_mul,
+
+            loot_mul: self.loot_mul * other.loot_mul,
         }
     }
 }
@@ -109,6 +155,8 @@ impl std::hash::Hash for Difficulty {
         hasher.write_u64((self.enemy_mul * 100.0) as u64);
         hasher.write_u64((self.enemy_health_mul * 100.0) as u64);
         hasher.write_u64((self.enemy_damage_mul * 100.0) as u64);
+
+        hasher.write_u64((self.loot_mul * 100.0) as u64);
     }
 }
 
@@ -1,22 +1,32 @@
-use cod::BoxChars;
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::{good, bad};
+use crate::bestiary::{Archetype, Bestiary};
+use crate::boss::{self, BossState, Phase};
+use crate::difficulty::Difficulty;
 use crate::difficulty::DifficultyMul;
 use crate::input::TurnResult;
 use crate::item::Item;
 use crate::map::{Direction, Map, Tile, TileKind, HEIGHT, WIDTH};
+use crate::path;
 use crate::player::Player;
+use crate::render::{self, Color};
+use crate::theme::{self, Sprite};
 use crate::world::World;
+use crate::{bad, good};
 
 const FOOD_MOVE_CHANCE: f32 = 0.55;
-const ENEMY_MOVE_CHANCE: f32 = 0.60;
+pub const ENEMY_MOVE_CHANCE: f32 = 0.60;
 
 const SPAWN_CHANCE: f32 = 0.5;
 const FOOD_SPAWN_CHANCE: f32 = 0.6;
 const ENEMY_SPAWN_CHANCE: f32 = 0.4;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+const ENEMY_PERCEPTION: u32 = 6;
+const ENEMY_GUARD_CHANCE: f32 = 0.3;
+const ENEMY_GUARD_RADIUS: u32 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Entity {
     pub x: u32,
     pub y: u32,
@@ -36,16 +46,19 @@ impl Entity {
         }
     }
 
-    pub fn spawn_random(world: &World) -> Option<Entity> {
+    /// Maybe spawns some food or enemies. Enemies from the world's
+    /// bestiary can spawn in packs, so this returns everything spawned.
+    pub fn spawn_random(world: &mut World) -> Vec<Entity> {
         let difficulty = world.difficulty * world.map.sector().difficulty;
 
         if world.entities.len() >= world.max_entities() as usize {
-            return None;
+            return Vec::new();
         }
 
-        let mut rng = thread_rng();
+        let spawn_chance = SPAWN_CHANCE * world.spawn_chance_coeff();
+        let rng = &mut world.rng;
         let r = rng.gen::<f32>();
-        if r <= SPAWN_CHANCE * world.spawn_chance_coeff() {
+        if r <= spawn_chance {
             let fsc = FOOD_SPAWN_CHANCE * difficulty.food_mul;
 
             let r = rng.gen::<f32>() * difficulty.food_mul * difficulty.enemy_mul;
@@ -54,55 +67,180 @@ impl Entity {
                     food: rng.gen_range(2..8).apply(difficulty.food_food_mul),
                 }
             } else if r - fsc <= ENEMY_SPAWN_CHANCE * difficulty.enemy_mul {
+                if !world.bestiary.is_empty() {
+                    return match Self::pick_archetype(world) {
+                        Some(archetype) => Self::spawn_pack(&archetype, difficulty, world),
+                        None => Vec::new(),
+                    };
+                }
+
+                let health = rng
+                    .gen_range(2..(world.player.damage() / 4).max(3))
+                    .apply(difficulty.enemy_health_mul);
                 EntityKind::Enemy {
-                    health: rng
-                        .gen_range(2..(world.player.damage / 4).max(3))
-                        .apply(difficulty.enemy_health_mul),
+                    health,
                     damage: rng
                         .gen_range(1..(world.player.health / 3).max(2))
                         .apply(difficulty.enemy_damage_mul),
+                    ai: Ai {
+                        behaviour: Behaviour::Chase,
+                        perception: ENEMY_PERCEPTION,
+                        flee_below: health / 3,
+                    },
+                    archetype: None,
                 }
             } else {
-                return None;
+                return Vec::new();
             };
 
-            let (x, y) = Self::pick_spawn_tile(&kind, world);
+            let Some((x, y)) = Self::pick_spawn_tile(&kind, world) else {
+                return Vec::new();
+            };
+
+            // some enemies stick to where they spawned
+            let mut kind = kind;
+            if let EntityKind::Enemy { ai, .. } = &mut kind {
+                if world.rng.gen::<f32>() <= ENEMY_GUARD_CHANCE {
+                    ai.behaviour = Behaviour::Guard {
+                        home: (x, y),
+                        radius: ENEMY_GUARD_RADIUS,
+                    };
+                }
+            }
 
-            Some(Entity {
+            vec![Entity {
                 x,
                 y,
                 kind,
                 alive: true,
                 persist: false,
-            })
+            }]
         } else {
-            None
+            Vec::new()
         }
     }
 
-    pub fn pick_spawn_tile(kind: &EntityKind, world: &World) -> (u32, u32) {
-        let mut rng = thread_rng();
-        // TODO: fix this filthy hack
-        let mut iterations = 0;
-        'outer: loop {
-            for y in (0..HEIGHT as u32).rev() {
-                for x in (0..WIDTH as u32).rev() {
-                    if world.player.x == x && world.player.y == y {
-                        continue;
-                    }
-                    let tile = world.map.get(x, y).unwrap();
+    /// Picks an archetype to spawn, weighted by how well the current
+    /// sector's tiles suit each one.
+    fn pick_archetype(world: &mut World) -> Option<Archetype> {
+        let tiles = world.map.tiles();
+        let weights: Vec<f32> = world
+            .bestiary
+            .archetypes()
+            .iter()
+            .map(|a| {
+                tiles
+                    .iter()
+                    .flatten()
+                    .map(|t| a.spawn_percentage(t.kind))
+                    .sum()
+            })
+            .collect();
 
-                    let mut chance = kind.spawn_percentage(&tile) / (WIDTH * HEIGHT) as f32;
-                    if chance == 0.0 {
-                        continue;
-                    }
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
 
-                    chance += iterations as f32 / (WIDTH * HEIGHT / 2) as f32;
+        let mut r = world.rng.gen::<f32>() * total;
+        for (archetype, weight) in world.bestiary.archetypes().iter().zip(weights) {
+            if r < weight {
+                return Some(archetype.clone());
+            }
+            r -= weight;
+        }
 
-                    let r: f32 = rng.gen();
-                    if r <= chance {
-                        break 'outer (x, y);
-                    }
+        world.bestiary.archetypes().last().cloned()
+    }
+
+    fn spawn_pack(archetype: &Archetype, difficulty: Difficulty, world: &mut World) -> Vec<Entity> {
+        let (min, max) = archetype.pack;
+        let size = world.rng.gen_range(min..=max.max(min));
+
+        let kind = Self::archetype_kind(archetype, difficulty, world);
+        let Some((x, y)) = Self::pick_spawn_tile(&kind, world) else {
+            return Vec::new();
+        };
+        let mut pack = vec![Entity::new(x, y, kind, false)];
+
+        let free: Vec<_> = path::neighbors(&world.map, x, y, true)
+            .filter(|&(x, y)| (x, y) != (world.player.x, world.player.y))
+            .filter(|&(x, y)| !world.entities.iter().any(|e| (e.x, e.y) == (x, y)))
+            .take(size.saturating_sub(1) as usize)
+            .collect();
+        for (x, y) in free {
+            let kind = Self::archetype_kind(archetype, difficulty, world);
+            pack.push(Entity::new(x, y, kind, false));
+        }
+
+        // guards watch over where they spawned
+        for entity in &mut pack {
+            if let EntityKind::Enemy { ai, .. } = &mut entity.kind {
+                if let Behaviour::Guard { home, .. } = &mut ai.behaviour {
+                    *home = (entity.x, entity.y);
+                }
+            }
+        }
+
+        pack
+    }
+
+    fn archetype_kind(
+        archetype: &Archetype,
+        difficulty: Difficulty,
+        world: &mut World,
+    ) -> EntityKind {
+        let (min, max) = archetype.health;
+        let health = world
+            .rng
+            .gen_range(min..=max.max(min))
+            .apply(difficulty.enemy_health_mul);
+        let (min, max) = archetype.damage;
+        let damage = world
+            .rng
+            .gen_range(min..=max.max(min))
+            .apply(difficulty.enemy_damage_mul);
+
+        EntityKind::Enemy {
+            health,
+            damage,
+            ai: archetype.ai.clone(),
+            archetype: Some(archetype.id.clone()),
+        }
+    }
+
+    /// Picks a tile for `kind` to spawn on, or `None` if it can't spawn
+    /// on any tile in the sector.
+    pub fn pick_spawn_tile(kind: &EntityKind, world: &mut World) -> Option<(u32, u32)> {
+        let mut candidates = Vec::new();
+        for y in (0..HEIGHT as u32).rev() {
+            for x in (0..WIDTH as u32).rev() {
+                if world.player.x == x && world.player.y == y {
+                    continue;
+                }
+                let tile = world.map.get(x, y).unwrap();
+
+                let chance =
+                    kind.spawn_percentage(&tile, &world.bestiary) / (WIDTH * HEIGHT) as f32;
+                if chance > 0.0 {
+                    candidates.push((x, y, chance));
+                }
+            }
+        }
+
+        if candidates.is_empty() {
+            return None;
+        }
+
+        // TODO: fix this filthy hack
+        let mut iterations = 0;
+        loop {
+            for &(x, y, chance) in &candidates {
+                let chance = chance + iterations as f32 / (WIDTH * HEIGHT / 2) as f32;
+
+                let r: f32 = world.rng.gen();
+                if r <= chance {
+                    return Some((x, y));
                 }
             }
 
@@ -110,28 +248,27 @@ impl Entity {
         }
     }
 
-    pub fn interact(&mut self, player: &mut Player, map: &mut Map) -> TurnResult {
+    pub fn interact(
+        &mut self,
+        player: &mut Player,
+        map: &mut Map,
+        rng: &mut impl Rng,
+    ) -> TurnResult {
         match &mut self.kind {
             EntityKind::Food { food } => {
-                player.hunger = player.hunger.saturating_sub(*food);
-                player.health = (player.health + 2).min(player.max_health);
+                player.eat(*food);
+                player.heal(2);
                 self.alive = false;
 
                 good!(Ate, *food)
             }
 
-            EntityKind::Enemy { health, damage } => {
-                if player.damage >= *health {
+            EntityKind::Enemy { health, damage, .. } => {
+                if player.damage() >= *health {
                     self.alive = false;
-                    good!(WonFight, false)
-                } else if *damage >= player.health {
-                    player.health = 0;
-                    bad!(ViolentDeath)
+                    good!(WonFight, false, Vec::new())
                 } else {
-                    *health -= player.damage;
-                    player.health -= *damage;
-
-                    good!(Fight, *damage, *health)
+                    Self::trade_blows(player, health, *damage, rng)
                 }
             }
 
@@ -141,35 +278,37 @@ impl Entity {
                 damage_gain,
                 block,
                 id,
+                ..
             } => {
-                if player.damage >= *health {
+                if player.damage() >= *health {
                     self.alive = false;
                     player.damage += *damage_gain;
 
                     let (dir, tile) = block;
                     let diff = dir.diff();
 
-                    // wrapping to allow across-the-map setups
-                    let x = self.x.wrapping_add_signed(diff.0);
-                    let y = self.y.wrapping_add_signed(diff.1);
-                    map.set(x, y, *tile);
+                    // blocks off the edge of the map are rejected when
+                    // the world is loaded
+                    if let (Some(x), Some(y)) = (
+                        self.x.checked_add_signed(diff.0),
+                        self.y.checked_add_signed(diff.1),
+                    ) {
+                        map.set(x, y, *tile);
+                    }
                     good!(DefeatedBoss, *id)
-                } else if *damage >= player.health {
-                    player.health = 0;
-                    bad!(ViolentDeath)
                 } else {
-                    *health -= player.damage;
-                    player.health -= *damage;
-
-                    good!(Fight, *damage, *health)
+                    Self::trade_blows(player, health, *damage, rng)
                 }
             }
 
             EntityKind::Item(item) => {
+                if !player.can_carry(item) {
+                    return good!(InventoryFull);
+                }
+
                 self.alive = false;
-                item.apply(player);
                 let name = item.name.clone();
-                player.inventory.push(std::mem::take(item));
+                player.add_item(item.clone());
 
                 good!(PickedUpItem, name)
             }
@@ -187,10 +326,9 @@ impl Entity {
                         let (item, item_idx) = &items[0];
 
                         // prevent farming items via saves
-                        if item_idx == idx && !player.inventory.iter().any(|i| i.name == item.name) {
+                        if item_idx == idx && !player.has_item(&item.name) {
                             let (item, _) = items.remove(0);
-                            item.apply(player);
-                            player.inventory.push(item);
+                            player.add_item(item);
                         }
                     }
 
@@ -201,26 +339,7 @@ impl Entity {
                         *dialogue_idx = None;
                     }
 
-                    let lines = speech.lines();
-                    let (width, height) = lines.fold((0, 0), |(w, h), l| (w.max(l.len()), h + 1));
-
-                    cod::color::de();
-                    cod::clear::rect(0, 0, width as u32 + 1, height + 1).unwrap();
-                    cod::rect_lines(
-                        BoxChars {
-                            horizontal: '-',
-                            vertical: '|',
-                            corner: '+',
-                        },
-                        0,
-                        0,
-                        width as u32 + 1,
-                        height + 1,
-                    ).unwrap();
-
-                    cod::blit(speech, 1, 1);
-                    cod::flush();
-                    cod::read::key();
+                    return good!(Dialogue, speech.clone());
                 }
 
                 good!(Menued)
@@ -228,45 +347,142 @@ impl Entity {
         }
     }
 
+    /// A round of a fight the player doesn't win outright: they hit the
+    /// enemy, and it hits back unless they dodge.
+    fn trade_blows(
+        player: &mut Player,
+        health: &mut u32,
+        damage: u32,
+        rng: &mut impl Rng,
+    ) -> TurnResult {
+        let taken = player.incoming(damage, rng);
+        if taken.is_some_and(|taken| taken >= player.health) {
+            player.health = 0;
+            return bad!(ViolentDeath);
+        }
+
+        *health -= player.damage();
+        match taken {
+            Some(taken) => {
+                player.health -= taken;
+                good!(Fight, taken, *health)
+            }
+            None => good!(Dodged, *health),
+        }
+    }
+
     pub fn ai(&mut self, world: &mut World) -> TurnResult {
-        let mut rng = thread_rng();
         match &mut self.kind {
             EntityKind::Food { .. } => {
-                let move_chance: f32 = rng.gen();
+                let move_chance: f32 = world.rng.gen();
                 if move_chance <= FOOD_MOVE_CHANCE {
-                    self.random_move(false, world, &mut rng);
+                    self.random_move(false, world);
                 }
             }
-            EntityKind::Enemy { health, damage } => {
+            EntityKind::Enemy {
+                health,
+                damage,
+                ai,
+                archetype,
+            } => {
                 let health_coeff = (*health as f32).tanh() / 2.0 + 0.5;
                 let damage_coeff = (*damage as f32).tanh() / 2.0 + 0.5;
-
-                let move_chance =
-                    rng.gen::<f32>() * health_coeff * damage_coeff * ENEMY_MOVE_CHANCE;
-                if move_chance <= ENEMY_MOVE_CHANCE {
-                    let (x, y) = self.random_move(true, world, &mut rng);
+                let fleeing = *health <= ai.flee_below;
+                let ai = ai.clone();
+                let archetype = archetype.as_deref().and_then(|id| world.bestiary.get(id));
+
+                let moves = match archetype {
+                    Some(archetype) => world.rng.gen::<f32>() <= archetype.move_chance,
+                    None => {
+                        let move_chance = world.rng.gen::<f32>()
+                            * health_coeff
+                            * damage_coeff
+                            * ENEMY_MOVE_CHANCE;
+                        move_chance <= ENEMY_MOVE_CHANCE
+                    }
+                };
+                if moves {
+                    let (x, y) = self.enemy_move(&ai, fleeing, world);
                     if world.player.x == x && world.player.y == y {
                         let mut player = world.player.clone();
                         let mut map = world.map.clone();
-                        let res = self.interact(&mut player, &mut map);
+                        let res = self.interact(&mut player, &mut map, &mut world.rng);
                         world.player = player;
                         world.map = map;
                         return res;
                     }
                 }
             }
-            EntityKind::Boss { .. } | EntityKind::Item(_) | EntityKind::Npc { .. } => {}
+            EntityKind::Boss {
+                health,
+                damage,
+                phases,
+                state,
+                ..
+            } => {
+                return boss::act((self.x, self.y), *health, damage, phases, state, world);
+            }
+            EntityKind::Item(_) | EntityKind::Npc { .. } => {}
         }
 
         good!()
     }
 
-    pub fn random_move(
-        &mut self,
-        into_player: bool,
-        world: &World,
-        rng: &mut impl Rng,
-    ) -> (u32, u32) {
+    /// Moves an enemy according to its AI: towards the player if it can
+    /// see them, away from them if it's fleeing, back home if it's
+    /// strayed too far, and randomly otherwise.
+    fn enemy_move(&mut self, ai: &Ai, fleeing: bool, world: &mut World) -> (u32, u32) {
+        if ai.behaviour == Behaviour::Wander {
+            return self.random_move(true, world);
+        }
+
+        let (sx, sy) = (self.x, self.y);
+        let occupied = |x: u32, y: u32| {
+            (x, y) != (sx, sy) && world.entities.iter().any(|e| (e.x, e.y) == (x, y))
+        };
+
+        let player = (world.player.x, world.player.y);
+        let from_player = path::distances(&world.map, player, world.diagonal, occupied);
+        let sees_player =
+            path::get(&from_player, self.x, self.y).is_some_and(|d| d <= ai.perception);
+
+        let home = match ai.behaviour {
+            Behaviour::Guard { home, radius } => Some((home, radius)),
+            _ => None,
+        };
+        // guards only give chase inside their area
+        let player_in_range = home.is_none_or(|((hx, hy), radius)| {
+            player.0.abs_diff(hx).max(player.1.abs_diff(hy)) <= radius
+        });
+
+        let step = if sees_player && fleeing {
+            path::step_away(&world.map, &from_player, self.x, self.y, world.diagonal)
+        } else if sees_player && player_in_range {
+            path::step_towards(&world.map, &from_player, self.x, self.y, world.diagonal)
+        } else if let Some(((hx, hy), radius)) = home {
+            if self.x.abs_diff(hx).max(self.y.abs_diff(hy)) > radius {
+                let to_home = path::distances(&world.map, (hx, hy), world.diagonal, |x, y| {
+                    occupied(x, y) || (x, y) == player
+                });
+                path::step_towards(&world.map, &to_home, self.x, self.y, world.diagonal)
+            } else {
+                return self.random_move(false, world);
+            }
+        } else {
+            return self.random_move(true, world);
+        };
+
+        if let Some((x, y)) = step {
+            if !occupied(x, y) {
+                self.x = x;
+                self.y = y;
+            }
+        }
+
+        (self.x, self.y)
+    }
+
+    pub fn random_move(&mut self, into_player: bool, world: &mut World) -> (u32, u32) {
         let mut iterations = 0;
         let mut x;
         let mut y;
@@ -276,7 +492,7 @@ impl Entity {
             }
             iterations += 1;
 
-            let diff = rng.gen::<Direction>().diff();
+            let diff = Direction::random(&mut world.rng, world.diagonal).diff();
             x = self.x.saturating_add_signed(diff.0);
             y = self.y.saturating_add_signed(diff.1);
 
@@ -291,9 +507,8 @@ impl Entity {
                 continue;
             };
 
-            match tile.kind {
-                TileKind::Water | TileKind::Mountain | TileKind::Village | TileKind::Building => continue,
-                _ => break,
+            if tile.kind.walkable() {
+                break;
             }
         }
 
@@ -305,27 +520,36 @@ impl Entity {
 
     pub fn id(&self) -> Option<u32> {
         Some(match &self.kind {
-            EntityKind::Boss { id, .. }
-            | EntityKind::Npc { id, .. } => *id,
-            EntityKind::Item(item) => item.id,
+            EntityKind::Boss { id, .. } | EntityKind::Npc { id, .. } => *id,
+            EntityKind::Item(item) if item.id != 0 => item.id,
             _ => None?,
         })
     }
 
-    pub fn draw(&self, x: u32, y: u32) {
-        let x = self.x * 2 + x + 1;
+    pub fn draw(&self, x: u32, y: u32, bestiary: &Bestiary) {
+        if let EntityKind::Boss { state, .. } = &self.kind {
+            render::de_bg();
+            render::fg(196);
+            for (tx, ty) in &state.telegraph {
+                render::pixel('*', tx * 2 + x + 1, ty + y);
+            }
+        }
+
+        // a wide sprite covers the whole tile
+        let sprite = self.kind.sprite(bestiary);
+        let x = self.x * 2 + x + 2 - render::char_width(sprite);
 
         match self.kind {
-            EntityKind::Boss { .. } => cod::color::bg(9),
-            _ => cod::color::de_bg(),
+            EntityKind::Boss { .. } => render::bg(9),
+            _ => render::de_bg(),
         }
 
-        cod::color::fg(self.kind.color());
-        cod::pixel(self.kind.sprite(), x, y + self.y);
+        render::fg(self.kind.color(bestiary));
+        render::pixel(sprite, x, y + self.y);
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum EntityKind {
     Food {
         food: u32,
@@ -333,6 +557,11 @@ pub enum EntityKind {
     Enemy {
         health: u32,
         damage: u32,
+        #[serde(default)]
+        ai: Ai,
+        /// The id of the enemy's type in the bestiary, if it has one.
+        #[serde(default)]
+        archetype: Option<String>,
     },
     Boss {
         health: u32,
@@ -340,20 +569,60 @@ pub enum EntityKind {
         id: u32,
         damage_gain: u32,
         block: (Direction, Tile),
+        #[serde(default)]
+        phases: Vec<Phase>,
+        #[serde(default)]
+        state: BossState,
     },
     Item(Item),
     Npc {
-        dialogue: &'static [&'static str],
+        dialogue: Vec<String>,
         dialogue_idx: Option<usize>,
         items: Vec<(Item, usize)>,
         id: u32,
     },
 }
 
+/// How an enemy decides where to move.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Behaviour {
+    /// Walks around at random.
+    #[default]
+    Wander,
+    /// Chases the player when it sees them.
+    Chase,
+    /// Chases the player only while they're within `radius` tiles of
+    /// `home`, and returns there when it strays too far. Spawned enemies
+    /// use their spawn point as their home.
+    Guard {
+        #[serde(default)]
+        home: (u32, u32),
+        radius: u32,
+    },
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ai {
+    pub behaviour: Behaviour,
+    /// How many moves away the enemy can see the player from.
+    pub perception: u32,
+    /// Once its health drops to this, the enemy runs from the player.
+    pub flee_below: u32,
+}
+
 impl EntityKind {
-    pub fn spawn_percentage(&self, tile: &Tile) -> f32 {
+    pub fn spawn_percentage(&self, tile: &Tile, bestiary: &Bestiary) -> f32 {
+        if let Some(archetype) = self.archetype(bestiary) {
+            return archetype.spawn_percentage(tile.kind);
+        }
+
         match tile.kind {
-            TileKind::Water | TileKind::Mountain | TileKind::Road | TileKind::Village | TileKind::Building => 0.0,
+            TileKind::Water
+            | TileKind::Mountain
+            | TileKind::Road
+            | TileKind::Village
+            | TileKind::Building => 0.0,
             TileKind::Grass => match self {
                 EntityKind::Food { .. } => 0.75,
                 EntityKind::Enemy { .. } => 0.15,
@@ -372,23 +641,53 @@ impl EntityKind {
         }
     }
 
-    pub fn color(&self) -> u8 {
+    /// The enemy's type, if it has one.
+    pub fn archetype<'a>(&self, bestiary: &'a Bestiary) -> Option<&'a Archetype> {
         match self {
-            Self::Food { .. } => 108,
-            Self::Enemy { .. } => 210,
-            Self::Boss { .. } => 136,
-            Self::Item(_) => 56,
-            Self::Npc { .. } => 79,
+            Self::Enemy {
+                archetype: Some(id),
+                ..
+            } => bestiary.get(id),
+            _ => None,
         }
     }
 
-    pub fn sprite(&self) -> char {
-        match self {
-            Self::Food { .. } => '+',
-            Self::Enemy { .. } => '!',
-            Self::Boss { .. } => '#',
-            Self::Item(_) => '?',
-            Self::Npc { .. } => '&',
+    pub fn color(&self, bestiary: &Bestiary) -> Color {
+        let theme = theme::get();
+        match self.archetype(bestiary) {
+            Some(archetype) => theme
+                .enemy(&archetype.id)
+                .color
+                .unwrap_or(archetype.color.into()),
+            None => theme.color(Sprite::of(self)),
+        }
+    }
+
+    pub fn sprite(&self, bestiary: &Bestiary) -> char {
+        let theme = theme::get();
+        match self.archetype(bestiary) {
+            Some(archetype) => theme.enemy(&archetype.id).glyph.unwrap_or(archetype.sprite),
+            None => theme.glyph(Sprite::of(self)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world_map;
+
+    #[test]
+    fn spawns_skip_sectors_without_suitable_tiles() {
+        let mut world = World::new(world_map::builtin().unwrap(), 1);
+
+        let food = EntityKind::Food { food: 2 };
+        let (x, y) = Entity::pick_spawn_tile(&food, &mut world).unwrap();
+        assert!((x, y) != (world.player.x, world.player.y));
+        let tile = world.map.get(x, y).unwrap();
+        assert!(food.spawn_percentage(&tile, &world.bestiary) > 0.0);
+
+        let item = EntityKind::Item(Item::default());
+        assert_eq!(Entity::pick_spawn_tile(&item, &mut world), None);
+    }
+}
@@ -1,10 +1,22 @@
 use cod::{BoxChars, Key};
 
+use crate::action::Action;
+use crate::config::Settings;
 use crate::difficulty::Difficulty;
+use crate::item::Slot;
+use crate::keymap::{Command, Keymap};
 use crate::map::Direction;
-use crate::save;
+use crate::menu;
+use crate::render;
 use crate::sector::HEIGHT;
-use crate::world::World;
+use crate::slots;
+use crate::status::StatusKind;
+use crate::term;
+use crate::world::{GameMode, World};
+
+pub const QUICKSAVE_SLOT: &str = "quicksave";
+pub const AUTOSAVE_SLOT: &str = "autosave";
+pub const IRONMAN_SLOT: &str = "ironman";
 
 #[macro_export]
 macro_rules! good {
@@ -19,156 +31,276 @@ macro_rules! bad {
     ( $kind:ident, $( $arg:expr ),* ) => { $crate::input::BadResult::$kind($($arg,)+).into() };
 }
 
-pub fn handle(world: &mut World) -> TurnResult {
-    if let Some(key) = cod::read::key() {
-        match key {
-            Key::ArrowUp => world.go(Direction::Up),
-            Key::ArrowDown => world.go(Direction::Down),
-            Key::ArrowLeft => world.go(Direction::Left),
-            Key::ArrowRight => world.go(Direction::Right),
-            Key::Char('q') | Key::Char('\x04') => bad!(Quit),
-            Key::Char(' ') => world.interact(),
-            Key::Char('s') => {
-                world.draw_message("Saving game", 3);
-                if save::save(world) {
-                    good!(Saved)
-                } else {
-                    good!()
+/// Reads a key and acts on it according to the keymap. Game actions
+/// are passed on to `World::update`; menus and prompts are handled here.
+pub fn handle(world: &mut World, settings: &Settings, keymap: &mut Keymap) -> UpdateResult {
+    let Some(key) = term::key() else {
+        if term::resized() {
+            return Ok(vec![GoodResult::Resized]);
+        }
+        return Ok(vec![GoodResult::NoKey]);
+    };
+
+    let Some(command) = keymap.command(&key) else {
+        return Ok(vec![GoodResult::InvalidKey(key)]);
+    };
+
+    let action = match command {
+        Command::Up => Action::Move(Direction::Up),
+        Command::Down => Action::Move(Direction::Down),
+        Command::Left => Action::Move(Direction::Left),
+        Command::Right => Action::Move(Direction::Right),
+        Command::UpLeft => Action::Move(Direction::UpLeft),
+        Command::UpRight => Action::Move(Direction::UpRight),
+        Command::DownLeft => Action::Move(Direction::DownLeft),
+        Command::DownRight => Action::Move(Direction::DownRight),
+        Command::Quit => Action::Quit,
+        Command::Interact => Action::Interact,
+        Command::Save | Command::Load | Command::QuickSave | Command::QuickLoad
+            if world.mode == GameMode::Ironman =>
+        {
+            return Ok(vec![GoodResult::Ironman]);
+        }
+        Command::Save => {
+            return Ok(if slots::save(world, settings.backups) {
+                vec![GoodResult::Saved]
+            } else {
+                Vec::new()
+            });
+        }
+        Command::Load => {
+            return Ok(if slots::load(world) {
+                // the recording can't reproduce the jump to a save
+                if let Some(replay) = &mut world.recording {
+                    replay.stop();
                 }
-            }
-            Key::Char('l') => {
-                world.draw_message("Loading game", 3);
-                if save::load(world) {
-                    good!(Loaded)
+                vec![GoodResult::Loaded]
+            } else {
+                Vec::new()
+            });
+        }
+        Command::QuickSave => {
+            return Ok(
+                if slots::save_quick(world, QUICKSAVE_SLOT, settings.backups) {
+                    vec![GoodResult::Saved]
                 } else {
-                    good!()
+                    Vec::new()
+                },
+            );
+        }
+        Command::QuickLoad => {
+            return Ok(if slots::load_quick(world, QUICKSAVE_SLOT) {
+                if let Some(replay) = &mut world.recording {
+                    replay.stop();
                 }
-            }
-            Key::Char('i') => {
-                let mut items = world.draw_inventory_full();
-                let mut cap = items.len().saturating_sub(1);
+                vec![GoodResult::Loaded]
+            } else {
+                Vec::new()
+            });
+        }
+        Command::Inventory => return inventory(world, keymap),
+        Command::MessageLog => return message_log(world, keymap),
+        Command::Difficulty => return difficulty(world),
+        Command::KeyBindings => {
+            menu::key_bindings(keymap);
+            return Ok(vec![GoodResult::Menued]);
+        }
+    };
 
-                let mut item_id = 0;
-                let mut last_y = None;
+    world.update(action)
+}
 
-                if let Some(y) = items.get(item_id).copied() {
-                    cod::color::de();
-                    cod::pixel('-', 1, y);
-                    cod::flush();
+fn inventory(world: &mut World, keymap: &Keymap) -> UpdateResult {
+    // the equipment slots come first, then everything carried
+    let mut selected = 0;
+    // shown once the inventory is closed
+    let mut events = vec![GoodResult::Menued];
 
-                    last_y = Some(y);
-                }
+    loop {
+        let cap = Slot::ALL.len() + world.player.inventory.len() - 1;
+        selected = selected.min(cap);
+        world.draw_inventory_full(selected);
 
-                while let Some(key) = cod::read::key() {
-                    match key {
-                        Key::ArrowUp => item_id = item_id.saturating_sub(1),
-                        Key::ArrowDown => item_id = (item_id + 1).min(cap),
-
-                        Key::Char('d' | 'D') => {
-                            let msg = "Remove (y/N)?";
-
-                            let name = &world.player.inventory[item_id].name;
-                            let width = (name.len().max(msg.len()) + 4) as u32;
-
-                            cod::clear::rect(1, 1, width + 1, 4).unwrap();
-
-                            cod::color::fg(1);
-                            cod::rect_lines(
-                                BoxChars {
-                                    horizontal: '-',
-                                    vertical: '|',
-                                    corner: '+',
-                                },
-                                1,
-                                1,
-                                width + 1,
-                                4,
-                            )
-                            .unwrap();
-
-                            cod::color::de_fg();
-                            cod::blit(msg, 3, 2);
-                            cod::blit(name, 3, 3);
-                            cod::goto::bot();
-                            cod::flush();
-
-                            if matches!(cod::read::key(), Some(Key::Char('y' | 'Y'))) {
-                                let item = world.player.inventory.remove(item_id);
-                                item.unapply(&mut world.player);
-                                item_id = item_id.saturating_sub(1);
-                            }
-
-                            items = world.draw_inventory_full();
-                            cap = items.len().saturating_sub(1);
-                        }
-
-                        Key::Char('q' | 'Q') | Key::Escape => break,
-                        _ => continue,
-                    }
-
-                    if let Some(y) = items.get(item_id).copied() {
-                        if let Some(y) = last_y {
-                            cod::pixel(' ', 1, y);
-                        }
-
-                        cod::color::de();
-                        cod::pixel('-', 1, y);
-
-                        cod::flush();
-
-                        last_y = Some(y);
-                    }
-                }
+        let Some(key) = term::key() else {
+            break;
+        };
 
-                good!(Menued)
+        // moving and quitting use the player's own keys, as well as the
+        // arrows and q
+        match (keymap.command(&key), &key) {
+            (Some(Command::Up), _) | (_, Key::ArrowUp) => {
+                selected = selected.saturating_sub(1);
+                continue;
             }
-            Key::Char('d') => {
-                cod::goto::pos(0, HEIGHT as u32);
-                cod::clear::line();
-                print!("Difficulty (easy, normal hard): ");
-                cod::flush();
-                let diff_str = cod::read::line();
-                let difficulty = diff_str.as_ref().and_then(|d| {
-                    Some(match d.to_lowercase().as_str() {
-                        "easy" => Difficulty::easy(),
-                        "normal" => Difficulty::normal(),
-                        "hard" => Difficulty::hard(),
-                        _ => None?,
-                    })
-                });
-
-                if let Some(difficulty) = difficulty {
-                    world.difficulty = difficulty;
-                    world.draw_message(format!("Set difficulty to {}", diff_str.unwrap()), 2);
-                    cod::read::key();
-                } else {
-                    world.draw_message("Invalid difficulty", 1);
+            (Some(Command::Down), _) | (_, Key::ArrowDown) => {
+                selected = (selected + 1).min(cap);
+                continue;
+            }
+            (Some(Command::Quit), _) => break,
+            _ => {}
+        }
+
+        let carried = selected.checked_sub(Slot::ALL.len());
+        let action = match (key, carried) {
+            (Key::Char('e' | 'E') | Key::Enter, None) => Action::Unequip(Slot::ALL[selected]),
+            (Key::Char('e' | 'E'), Some(i)) => Action::Equip(i),
+            (Key::Char('u' | 'U'), Some(i)) => Action::Use(i),
+            (Key::Enter, Some(i)) => match world.player.inventory.get(i) {
+                Some(item) if item.consumable.is_some() => Action::Use(i),
+                Some(item) if item.slot.is_some() => Action::Equip(i),
+                _ => continue,
+            },
+
+            (Key::Char('d' | 'D'), Some(i)) => {
+                let Some(item) = world.player.inventory.get(i) else {
+                    continue;
+                };
+
+                let msg = "Drop (y/N)?";
+
+                let name = &item.name;
+                let width = (name.len().max(msg.len()) + 4) as u32;
+
+                render::de();
+                render::clear_rect(1, 1, width + 1, 4);
+
+                render::fg(1);
+                render::rect_lines(
+                    BoxChars {
+                        horizontal: '-',
+                        vertical: '|',
+                        corner: '+',
+                    },
+                    1,
+                    1,
+                    width + 1,
+                    4,
+                );
+
+                render::de_fg();
+                render::blit(msg, 3, 2);
+                render::blit(name, 3, 3);
+                render::flush();
+
+                if !matches!(term::key(), Some(Key::Char('y' | 'Y'))) {
+                    continue;
                 }
+                Action::Drop(i)
+            }
+
+            (Key::Char('q' | 'Q') | Key::Escape, _) => break,
+            _ => continue,
+        };
+
+        events = world.update(action)?;
+    }
+
+    Ok(events)
+}
 
-                good!(Menued)
+/// Shows the whole message log until it's closed, newest at the
+/// bottom.
+fn message_log(world: &World, keymap: &Keymap) -> UpdateResult {
+    // how many of the newest messages are scrolled out of view
+    let mut skip = 0;
+
+    loop {
+        let page = world.log.draw_full(skip) as usize;
+        let max = world.log.len().saturating_sub(page);
+        skip = skip.min(max);
+
+        let Some(key) = term::key() else {
+            if term::resized() {
+                continue;
             }
-            _ => good!(InvalidKey, key)
+            break;
+        };
+
+        skip = match (keymap.command(&key), key) {
+            (Some(Command::Up), _) | (_, Key::ArrowUp) => skip + 1,
+            (Some(Command::Down), _) | (_, Key::ArrowDown) => skip.saturating_sub(1),
+            (Some(Command::Quit), _) | (_, Key::Char('q' | 'Q') | Key::Escape) => break,
+            (_, Key::PageUp) => skip + page,
+            (_, Key::PageDown) => skip.saturating_sub(page),
+            (_, Key::Home) => max,
+            (_, Key::End) => 0,
+            _ => skip,
         }
+        .min(max);
+    }
+
+    Ok(vec![GoodResult::Menued])
+}
+
+fn difficulty(world: &mut World) -> UpdateResult {
+    cod::goto::pos(0, HEIGHT as u32);
+    cod::clear::line();
+    print!("Difficulty (easy, normal hard): ");
+    cod::flush();
+    let diff_str = term::line();
+    // the prompt was written straight to the terminal
+    render::invalidate();
+    let difficulty = diff_str.as_ref().and_then(|d| {
+        Some(match d.to_lowercase().as_str() {
+            "easy" => Difficulty::easy(),
+            "normal" => Difficulty::normal(),
+            "hard" => Difficulty::hard(),
+            _ => None?,
+        })
+    });
+
+    if let Some(difficulty) = difficulty {
+        world.update(Action::SetDifficulty(difficulty))?;
+        world.message(format!("Set difficulty to {}", diff_str.unwrap()), 2);
     } else {
-        good!(NoKey)
+        world.message("Invalid difficulty", 1);
     }
+
+    Ok(vec![GoodResult::Menued])
 }
 
 pub type TurnResult = Result<GoodResult, BadResult>;
+pub type UpdateResult = Result<Vec<GoodResult>, BadResult>;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum GoodResult {
     Ok,
     NoKey,
     InvalidKey(Key),
+    /// The damage the player took, and the enemy's remaining health.
     Fight(u32, u32),
-    WonFight(bool),
+    /// The player dodged the enemy's hit; the enemy's remaining health.
+    Dodged(u32),
+    /// Whether the player got an upgrade, and the names of whatever
+    /// the enemy dropped.
+    WonFight(bool, Vec<String>),
     DefeatedBoss(u32),
+    BossPhase,
+    BossTelegraph,
+    BossHit(u32),
+    BossMissed,
     InvalidMove(Direction),
     PickedUpItem(String),
+    Dropped(String),
+    Used(String),
+    Equipped(String),
+    Unequipped(String),
+    NotEquippable(String),
+    InventoryFull,
+    NotUsable(String),
+    /// How many enemies a bomb hit, and what the ones it killed dropped.
+    Bombed(u32, Vec<String>),
+    Dialogue(String),
+    ThirstDamage,
+    PoisonDamage(u32),
+    StatusEnded(StatusKind),
     Saved,
+    Autosaved,
     Loaded,
+    Ironman,
     Menued,
+    /// The terminal was resized while waiting for a key.
+    Resized,
     WaterMove,
     Ate(u32),
 }
@@ -178,6 +310,7 @@ pub enum BadResult {
     HungerDeath,
     ThirstDeath,
     ViolentDeath,
+    PoisonDeath,
     Quit,
 }
 
@@ -192,4 +325,3 @@ impl From<BadResult> for TurnResult {
         Err(res)
     }
 }
-
@@ -1,40 +1,180 @@
 use serde::{Deserialize, Serialize};
 
-use crate::player::Player;
+use crate::player::{Player, Stat};
+use crate::render;
+use crate::status::StatusKind;
 
-#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Item {
     pub name: String,
+    #[serde(default)]
     pub buffs: Vec<Buff>,
+    #[serde(default)]
     pub debuffs: Vec<Buff>,
+    /// Unique across the world, so the item can be despawned for good
+    /// once picked up. 0 means the item has no id, e.g. dropped loot.
+    #[serde(default)]
     pub id: u32,
+    #[serde(default)]
+    pub rarity: Rarity,
+    /// If set, the item's buffs only apply while it's equipped in this
+    /// slot. Otherwise they apply as long as it's carried.
+    #[serde(default)]
+    pub slot: Option<Slot>,
+    /// If set, the item can be used up from the inventory.
+    #[serde(default)]
+    pub consumable: Option<Consumable>,
+    /// How many of the item are stacked together.
+    #[serde(default = "one")]
+    pub count: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+impl Default for Item {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            buffs: Vec::new(),
+            debuffs: Vec::new(),
+            id: 0,
+            rarity: Rarity::Common,
+            slot: None,
+            consumable: None,
+            count: 1,
+        }
+    }
+}
+
+/// Where an item is worn. Only one item can be equipped in each slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Slot {
+    Weapon,
+    Armor,
+    Trinket,
+}
+
+impl Slot {
+    pub const ALL: [Slot; 3] = [Self::Weapon, Self::Armor, Self::Trinket];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Weapon => "Weapon",
+            Self::Armor => "Armor",
+            Self::Trinket => "Trinket",
+        }
+    }
+}
+
+/// What happens when a consumable is used, and how many times it can be
+/// used before one is used up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Consumable {
+    pub effect: Effect,
+    #[serde(default = "one")]
+    pub uses: u32,
+    /// How many uses the top item of the stack has left.
+    #[serde(default)]
+    pub used: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Effect {
+    /// Takes this much off the player's hunger.
+    Food(u32),
+    /// Takes this much off the player's thirst.
+    Water(u32),
+    Heal(u32),
+    /// Damages every enemy within `radius` tiles. Bosses shrug it off.
+    Bomb {
+        radius: u32,
+        damage: u32,
+    },
+    /// Gives the player a status effect for a number of turns.
+    Status {
+        status: StatusKind,
+        turns: u32,
+    },
+}
+
+impl Effect {
+    fn draw(&self, x: u32, y: u32) {
+        let (color, text) = match self {
+            Self::Food(n) => (223, format!("eat: -{n} hunger")),
+            Self::Water(n) => (12, format!("drink: -{n} thirst")),
+            Self::Heal(n) => (1, format!("heal: +{n} health")),
+            Self::Bomb { radius, damage } => {
+                (208, format!("bomb: {damage} damage, radius {radius}"))
+            }
+            Self::Status { status, turns } => (
+                status.color(),
+                format!("{}: {turns} turns", status.name().to_lowercase()),
+            ),
+        };
+
+        render::goto(x, y);
+        render::de_bg();
+        render::fg(color);
+        render::print(text);
+    }
+}
+
+/// How rare an item is, which decides the color of its name.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum Rarity {
+    #[default]
+    Common,
+    Uncommon,
+    Rare,
+    Legendary,
+}
+
+impl Rarity {
+    pub fn color(&self) -> u8 {
+        match self {
+            Self::Common => 3,
+            Self::Uncommon => 2,
+            Self::Rare => 33,
+            Self::Legendary => 208,
+        }
+    }
 }
 
 impl Item {
+    #[allow(dead_code)]
     pub fn full(name: &str, id: u32, buffs: Vec<Buff>, debuffs: Vec<Buff>) -> Self {
         Self {
             name: name.to_string(),
             buffs,
             debuffs,
             id,
+            ..Default::default()
         }
     }
 
+    #[allow(dead_code)]
     pub fn buffs(name: &str, id: u32, buffs: Vec<Buff>) -> Self {
         Self {
             name: name.to_string(),
             buffs,
             debuffs: Vec::new(),
             id,
+            ..Default::default()
         }
     }
 
+    #[allow(dead_code)]
     pub fn basic(name: &str, id: u32, buff: Buff) -> Self {
         Self {
             name: name.to_string(),
             buffs: vec![buff],
             debuffs: Vec::new(),
             id,
+            ..Default::default()
         }
     }
 
@@ -47,6 +187,23 @@ impl Item {
         }
     }
 
+    /// Whether `other` is the same kind of item and can go in the same
+    /// stack. Only consumables without a unique id stack.
+    pub fn stacks_with(&self, other: &Item) -> bool {
+        let (Some(a), Some(b)) = (&self.consumable, &other.consumable) else {
+            return false;
+        };
+
+        self.id == 0
+            && other.id == 0
+            && self.name == other.name
+            && self.rarity == other.rarity
+            && self.buffs == other.buffs
+            && self.debuffs == other.debuffs
+            && a.effect == b.effect
+            && a.uses == b.uses
+    }
+
     pub fn unapply(&self, player: &mut Player) {
         for buff in &self.buffs {
             buff.apply(player, true);
@@ -60,13 +217,30 @@ impl Item {
     pub fn draw(&self, x: u32, mut y: u32) -> u32 {
         let oy = y;
 
-        cod::goto::pos(x, y);
-        cod::color::fg(3);
-        cod::color::de_bg();
-        println!("{}", self.name);
+        render::goto(x, y);
+        render::fg(self.rarity.color());
+        render::de_bg();
+        render::print(&self.name);
+        if self.count > 1 {
+            render::fg(8);
+            render::print(format!(" x{}", self.count));
+        }
 
         let x = x + 1;
         y += 1;
+        if let Some(consumable) = &self.consumable {
+            consumable.effect.draw(x, y);
+            if consumable.uses > 1 {
+                render::fg(8);
+                render::print(format!(
+                    " ({}/{} uses)",
+                    consumable.uses - consumable.used,
+                    consumable.uses
+                ));
+            }
+            y += 1;
+        }
+
         for buff in &self.buffs {
             buff.draw(x, y, false);
             y += 1;
@@ -87,45 +261,76 @@ pub enum Buff {
     Damage(u32),
     ThirstCap(u32),
     HungerCap(u32),
+    Armor(u32),
+    /// In percentage points.
+    Dodge(u32),
+    Vision(u32),
+    /// In percentage points.
+    Speed(u32),
+    /// Scales a stat by a percentage.
+    Percent(Stat, u32),
 }
 
 impl Buff {
     pub fn apply(&self, player: &mut Player, debuff: bool) {
-        let (stat, diff) = match self {
-            Self::MaxHealth(i) => (&mut player.max_health, i),
-            Self::Damage(i) => (&mut player.damage, i),
-            Self::ThirstCap(i) => (&mut player.thirst_cap, i),
-            Self::HungerCap(i) => (&mut player.hunger_cap, i),
-        };
-
         let diff = if debuff {
-            -(*diff as i32)
+            -(self.diff() as i32)
         } else {
-            *diff as i32
+            self.diff() as i32
+        };
+
+        let bonuses = match self {
+            Self::Percent(..) => &mut player.percent,
+            _ => &mut player.bonus,
         };
-        *stat = stat.saturating_add_signed(diff);
+        *bonuses.entry(self.stat()).or_default() += diff;
+    }
+
+    pub fn stat(&self) -> Stat {
+        match self {
+            Self::MaxHealth(_) => Stat::MaxHealth,
+            Self::Damage(_) => Stat::Damage,
+            Self::ThirstCap(_) => Stat::ThirstCap,
+            Self::HungerCap(_) => Stat::HungerCap,
+            Self::Armor(_) => Stat::Armor,
+            Self::Dodge(_) => Stat::Dodge,
+            Self::Vision(_) => Stat::Vision,
+            Self::Speed(_) => Stat::Speed,
+            Self::Percent(stat, _) => *stat,
+        }
     }
 
     pub fn diff(&self) -> u32 {
         match self {
-            Self::MaxHealth(d) | Self::Damage(d) | Self::ThirstCap(d) | Self::HungerCap(d) => *d,
+            Self::MaxHealth(d)
+            | Self::Damage(d)
+            | Self::ThirstCap(d)
+            | Self::HungerCap(d)
+            | Self::Armor(d)
+            | Self::Dodge(d)
+            | Self::Vision(d)
+            | Self::Speed(d)
+            | Self::Percent(_, d) => *d,
         }
     }
 
     pub fn draw(&self, x: u32, y: u32, debuff: bool) {
-        let (color, name) = match self {
-            Self::MaxHealth(_) => (1, "health"),
-            Self::Damage(_) => (7, "damage"),
-            Self::ThirstCap(_) => (12, "water"),
-            Self::HungerCap(_) => (223, "food"),
-        };
+        let stat = self.stat();
 
-        cod::goto::pos(x, y);
-        cod::color::de_bg();
-        cod::color::fg(color);
-        print!("{name}: ");
+        render::goto(x, y);
+        render::de_bg();
+        render::fg(stat.color());
+        render::print(format!("{}: ", stat.name()));
 
-        cod::color::fg(if debuff { 1 } else { 2 });
-        print!("{}{}", if debuff { '-' } else { '+' }, self.diff());
+        let unit = match self {
+            Self::Dodge(_) | Self::Speed(_) | Self::Percent(..) => "%",
+            _ => "",
+        };
+        render::fg(if debuff { 1 } else { 2 });
+        render::print(format!(
+            "{}{}{unit}",
+            if debuff { '-' } else { '+' },
+            self.diff()
+        ));
     }
 }
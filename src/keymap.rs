@@ -0,0 +1,405 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use cod::Key;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{config_dir, ConfigError};
+
+/// Something the player can do with a single key during the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    Up,
+    Down,
+    Left,
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+    Interact,
+    Inventory,
+    MessageLog,
+    Save,
+    Load,
+    QuickSave,
+    QuickLoad,
+    Difficulty,
+    KeyBindings,
+    Quit,
+}
+
+impl Command {
+    pub const ALL: [Command; 18] = [
+        Self::Up,
+        Self::Down,
+        Self::Left,
+        Self::Right,
+        Self::UpLeft,
+        Self::UpRight,
+        Self::DownLeft,
+        Self::DownRight,
+        Self::Interact,
+        Self::Inventory,
+        Self::MessageLog,
+        Self::Save,
+        Self::Load,
+        Self::QuickSave,
+        Self::QuickLoad,
+        Self::Difficulty,
+        Self::KeyBindings,
+        Self::Quit,
+    ];
+
+    /// The game can't be played without a key for each of these.
+    pub const REQUIRED: [Command; 5] = [Self::Up, Self::Down, Self::Left, Self::Right, Self::Quit];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Up => "Move up",
+            Self::Down => "Move down",
+            Self::Left => "Move left",
+            Self::Right => "Move right",
+            Self::UpLeft => "Move up-left",
+            Self::UpRight => "Move up-right",
+            Self::DownLeft => "Move down-left",
+            Self::DownRight => "Move down-right",
+            Self::Interact => "Interact",
+            Self::Inventory => "Inventory",
+            Self::MessageLog => "Message log",
+            Self::Save => "Save",
+            Self::Load => "Load",
+            Self::QuickSave => "Quicksave",
+            Self::QuickLoad => "Quickload",
+            Self::Difficulty => "Difficulty",
+            Self::KeyBindings => "Key bindings",
+            Self::Quit => "Quit",
+        }
+    }
+}
+
+pub const PRESETS: [&str; 4] = ["arrows", "wasd", "vi", "numpad"];
+
+type Preset = &'static [(Command, &'static [&'static str])];
+
+const ARROWS: Preset = &[
+    (Command::Up, &["Up"]),
+    (Command::Down, &["Down"]),
+    (Command::Left, &["Left"]),
+    (Command::Right, &["Right"]),
+    (Command::UpLeft, &["Home"]),
+    (Command::UpRight, &["PageUp"]),
+    (Command::DownLeft, &["End"]),
+    (Command::DownRight, &["PageDown"]),
+    (Command::Interact, &["Space"]),
+    (Command::Inventory, &["i"]),
+    (Command::MessageLog, &["M"]),
+    (Command::Save, &["s"]),
+    (Command::Load, &["l"]),
+    (Command::QuickSave, &["S"]),
+    (Command::QuickLoad, &["L"]),
+    (Command::Difficulty, &["d"]),
+    (Command::KeyBindings, &["K"]),
+    (Command::Quit, &["q", "^D"]),
+];
+
+const WASD: Preset = &[
+    (Command::Up, &["w", "Up"]),
+    (Command::Down, &["s", "Down"]),
+    (Command::Left, &["a", "Left"]),
+    (Command::Right, &["d", "Right"]),
+    (Command::UpLeft, &["q"]),
+    (Command::UpRight, &["e"]),
+    (Command::DownLeft, &["z"]),
+    (Command::DownRight, &["c"]),
+    (Command::Interact, &["Space", "f"]),
+    (Command::Inventory, &["i", "Tab"]),
+    (Command::MessageLog, &["M"]),
+    (Command::Save, &["o"]),
+    (Command::Load, &["p"]),
+    (Command::QuickSave, &["O"]),
+    (Command::QuickLoad, &["P"]),
+    (Command::Difficulty, &["m"]),
+    (Command::KeyBindings, &["K"]),
+    (Command::Quit, &["Q", "^D"]),
+];
+
+const VI: Preset = &[
+    (Command::Up, &["k"]),
+    (Command::Down, &["j"]),
+    (Command::Left, &["h"]),
+    (Command::Right, &["l"]),
+    (Command::UpLeft, &["y"]),
+    (Command::UpRight, &["u"]),
+    (Command::DownLeft, &["b"]),
+    (Command::DownRight, &["n"]),
+    (Command::Interact, &["Space", "."]),
+    (Command::Inventory, &["i"]),
+    (Command::MessageLog, &["M"]),
+    (Command::Save, &["w"]),
+    (Command::Load, &["e"]),
+    (Command::QuickSave, &["W"]),
+    (Command::QuickLoad, &["E"]),
+    (Command::Difficulty, &["d"]),
+    (Command::KeyBindings, &["K"]),
+    (Command::Quit, &["q", "^D"]),
+];
+
+// with num lock off, the numpad sends arrow keys and Home/PageUp/etc.
+const NUMPAD: Preset = &[
+    (Command::Up, &["8", "Up"]),
+    (Command::Down, &["2", "Down"]),
+    (Command::Left, &["4", "Left"]),
+    (Command::Right, &["6", "Right"]),
+    (Command::UpLeft, &["7", "Home"]),
+    (Command::UpRight, &["9", "PageUp"]),
+    (Command::DownLeft, &["1", "End"]),
+    (Command::DownRight, &["3", "PageDown"]),
+    (Command::Interact, &["5", "Space"]),
+    (Command::Inventory, &["i", "+"]),
+    (Command::MessageLog, &["M"]),
+    (Command::Save, &["s"]),
+    (Command::Load, &["l"]),
+    (Command::QuickSave, &["S"]),
+    (Command::QuickLoad, &["L"]),
+    (Command::Difficulty, &["d"]),
+    (Command::KeyBindings, &["K"]),
+    (Command::Quit, &["q", "^D"]),
+];
+
+/// Maps keys to the commands they trigger. A key triggers at most one
+/// command, but a command can have any number of keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: BTreeMap<Command, Vec<Key>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::preset("arrows").unwrap()
+    }
+}
+
+impl Keymap {
+    pub fn preset(name: &str) -> Option<Self> {
+        let preset = match name {
+            "arrows" => ARROWS,
+            "wasd" => WASD,
+            "vi" => VI,
+            "numpad" => NUMPAD,
+            _ => return None,
+        };
+
+        let bindings = preset
+            .iter()
+            .map(|(command, keys)| {
+                let keys = keys.iter().map(|k| parse_key(k).unwrap()).collect();
+                (*command, keys)
+            })
+            .collect();
+
+        Some(Self { bindings })
+    }
+
+    /// The command `key` is bound to, if any.
+    pub fn command(&self, key: &Key) -> Option<Command> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(key))
+            .map(|(command, _)| *command)
+    }
+
+    pub fn keys(&self, command: Command) -> &[Key] {
+        self.bindings.get(&command).map_or(&[], Vec::as_slice)
+    }
+
+    /// Binds `key` to `command`, unbinding it from whatever it was
+    /// bound to before.
+    pub fn bind(&mut self, command: Command, key: Key) {
+        for keys in self.bindings.values_mut() {
+            keys.retain(|k| *k != key);
+        }
+
+        self.bindings.entry(command).or_default().push(key);
+    }
+
+    pub fn clear(&mut self, command: Command) {
+        self.bindings.remove(&command);
+    }
+
+    /// A command the game can't do without that has no keys, if any.
+    pub fn unbound_required(&self) -> Option<Command> {
+        Command::REQUIRED
+            .into_iter()
+            .find(|command| self.keys(*command).is_empty())
+    }
+
+    /// Loads the keymap from its config file, creating it with the
+    /// default bindings if it doesn't exist yet. Commands missing from
+    /// the file are left unbound, unless they're required.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = Self::path();
+        let json = match fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let keymap = Self::default();
+                keymap.save()?;
+                return Ok(keymap);
+            }
+            Err(e) => return Err(ConfigError::Io(path, e)),
+        };
+
+        let names: BTreeMap<Command, Vec<String>> =
+            serde_json::from_str(&json).map_err(|e| ConfigError::Parse(path.clone(), e))?;
+
+        let mut keymap = Self {
+            bindings: BTreeMap::new(),
+        };
+        for (command, names) in names {
+            for name in names {
+                let Some(key) = parse_key(&name) else {
+                    return Err(ConfigError::Invalid(path, format!("unknown key `{name}`")));
+                };
+
+                if let Some(other) = keymap.command(&key) {
+                    return Err(ConfigError::Invalid(
+                        path,
+                        format!(
+                            "`{name}` is bound to both `{}` and `{}`",
+                            other.name(),
+                            command.name()
+                        ),
+                    ));
+                }
+
+                keymap.bindings.entry(command).or_default().push(key);
+            }
+        }
+
+        if let Some(command) = keymap.unbound_required() {
+            return Err(ConfigError::Invalid(
+                path,
+                format!("`{}` isn't bound to any key", command.name()),
+            ));
+        }
+
+        Ok(keymap)
+    }
+
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let names: BTreeMap<_, Vec<_>> = self
+            .bindings
+            .iter()
+            .map(|(command, keys)| (command, keys.iter().filter_map(key_name).collect()))
+            .collect();
+
+        let path = Self::path();
+        let json = serde_json::to_string_pretty(&names).expect("Failed to serialize keymap");
+        fs::write(&path, json).map_err(|e| ConfigError::Io(path, e))
+    }
+
+    fn path() -> PathBuf {
+        let mut path = config_dir();
+        path.push("keys.json");
+        path
+    }
+}
+
+/// The name a key is written as in the config file, e.g. `Up`, `^D`
+/// or `w`. Keys that aren't recognised well enough to tell apart (like
+/// function keys) have none.
+pub fn key_name(key: &Key) -> Option<String> {
+    let name = match key {
+        Key::ArrowUp => "Up".to_string(),
+        Key::ArrowDown => "Down".to_string(),
+        Key::ArrowLeft => "Left".to_string(),
+        Key::ArrowRight => "Right".to_string(),
+        Key::Enter => "Enter".to_string(),
+        Key::Escape => "Esc".to_string(),
+        Key::Backspace => "Backspace".to_string(),
+        Key::Home => "Home".to_string(),
+        Key::End => "End".to_string(),
+        Key::Tab => "Tab".to_string(),
+        Key::BackTab => "BackTab".to_string(),
+        Key::Del => "Del".to_string(),
+        Key::Insert => "Insert".to_string(),
+        Key::PageUp => "PageUp".to_string(),
+        Key::PageDown => "PageDown".to_string(),
+        Key::Char(' ') => "Space".to_string(),
+        Key::Char(c @ '\x01'..='\x1a') => format!("^{}", (*c as u8 + b'@') as char),
+        Key::Char(c) => c.to_string(),
+        _ => return None,
+    };
+    Some(name)
+}
+
+pub fn parse_key(name: &str) -> Option<Key> {
+    Some(match name {
+        "Up" => Key::ArrowUp,
+        "Down" => Key::ArrowDown,
+        "Left" => Key::ArrowLeft,
+        "Right" => Key::ArrowRight,
+        "Enter" => Key::Enter,
+        "Esc" => Key::Escape,
+        "Backspace" => Key::Backspace,
+        "Home" => Key::Home,
+        "End" => Key::End,
+        "Tab" => Key::Tab,
+        "BackTab" => Key::BackTab,
+        "Del" => Key::Del,
+        "Insert" => Key::Insert,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        "Space" => Key::Char(' '),
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next()?, chars.next(), chars.next()) {
+                ('^', Some(c @ 'A'..='Z'), None) => Key::Char((c as u8 - b'@') as char),
+                (c, None, None) => Key::Char(c),
+                _ => return None,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preset_keys_round_trip() {
+        for name in PRESETS {
+            let keymap = Keymap::preset(name).unwrap();
+            for command in Command::ALL {
+                for key in keymap.keys(command) {
+                    let name = key_name(key).unwrap();
+                    assert_eq!(parse_key(&name).as_ref(), Some(key));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unrecognised_keys_have_no_name() {
+        assert_eq!(key_name(&Key::Unknown), None);
+        assert_eq!(
+            key_name(&Key::UnknownEscSeq(vec!['[', '1', '5', '~'])),
+            None
+        );
+        assert_eq!(key_name(&Key::Alt), None);
+        assert_eq!(key_name(&Key::Char('?')).as_deref(), Some("?"));
+    }
+    #[test]
+    fn required_commands_are_bound() {
+        for name in PRESETS {
+            assert_eq!(Keymap::preset(name).unwrap().unbound_required(), None);
+        }
+
+        let mut keymap = Keymap::default();
+        keymap.clear(Command::Quit);
+        assert_eq!(keymap.unbound_required(), Some(Command::Quit));
+    }
+}
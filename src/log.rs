@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+
+use crate::render;
+
+/// How many messages are kept before the oldest are forgotten.
+const LIMIT: usize = 500;
+
+/// Something that happened, as it was told to the player.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// The turn it (last) happened on.
+    pub turn: u32,
+    pub text: String,
+    pub color: u8,
+    /// How many times in a row it happened.
+    pub count: u32,
+}
+
+impl Message {
+    /// Draws the message on one line, cut off after `width` columns.
+    fn draw(&self, x: u32, y: u32, width: u32) {
+        let stamp = format!("{:>5} ", self.turn);
+        let mut text = self.text.clone();
+        if self.count > 1 {
+            text += &format!(" (x{})", self.count);
+        }
+
+        let mut room = width.saturating_sub(render::width(&stamp));
+        let text: String = text
+            .chars()
+            .take_while(|&ch| {
+                let fits = render::char_width(ch) <= room;
+                room = room.saturating_sub(render::char_width(ch));
+                fits
+            })
+            .collect();
+
+        render::fg(8);
+        render::blit(stamp, x, y);
+        render::fg(self.color);
+        render::print(text);
+    }
+}
+
+/// Everything the player's been told recently, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct Log {
+    messages: VecDeque<Message>,
+}
+
+impl Log {
+    /// Adds a message. If it's the same as the last one, that one is
+    /// counted again instead.
+    pub fn push(&mut self, turn: u32, text: impl Display, color: u8) {
+        let text = text.to_string();
+        if let Some(last) = self.messages.back_mut() {
+            if last.text == text && last.color == color {
+                last.turn = turn;
+                last.count += 1;
+                return;
+            }
+        }
+
+        if self.messages.len() == LIMIT {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(Message {
+            turn,
+            text,
+            color,
+            count: 1,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Draws up to `lines` messages, oldest at the top, leaving out the
+    /// newest `skip`.
+    pub fn draw(&self, x: u32, y: u32, width: u32, lines: u32, skip: usize) {
+        let end = self.len().saturating_sub(skip);
+        let start = end.saturating_sub(lines as usize);
+        for (i, message) in self.messages.range(start..end).enumerate() {
+            message.draw(x, y + i as u32, width);
+        }
+    }
+
+    /// Draws the log screen, scrolled up past the newest `skip`
+    /// messages. Returns the number of messages that fit on it.
+    pub fn draw_full(&self, skip: usize) -> u32 {
+        render::clear();
+        let (width, height) = render::size();
+        let lines = height.saturating_sub(5).max(1);
+
+        render::de();
+        render::pixel('+', 0, 0);
+        render::orth_line('|', 0, 1, 0, lines + 1);
+        render::pixel('+', 0, lines + 2);
+        render::blit("- Message log -+", 1, 0);
+
+        if self.is_empty() {
+            render::fg(8);
+            render::blit("Nothing's happened yet", 2, 1);
+        }
+        self.draw(2, 1, width.saturating_sub(2), lines, skip);
+
+        render::fg(8);
+        let end = self.len().saturating_sub(skip);
+        let start = end.saturating_sub(lines as usize);
+        render::blit(
+            format!(
+                "{}-{} of {}  Up/Down: scroll  PageUp/PageDown: page  q: back",
+                (start + 1).min(end),
+                end,
+                self.len()
+            ),
+            2,
+            lines + 3,
+        );
+        render::flush();
+
+        lines
+    }
+}
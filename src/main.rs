@@ -1,40 +1,339 @@
+mod action;
+mod bestiary;
+mod boss;
+mod cli;
+mod config;
 mod difficulty;
 mod entity;
 mod input;
 mod item;
+mod keymap;
+mod log;
 mod map;
+mod menu;
+mod palette;
+mod path;
 mod player;
 mod quip;
+mod render;
+mod replay;
 mod save;
 mod sector;
+mod slots;
+mod status;
+mod term;
+mod theme;
 mod title;
 mod world;
 mod world_map;
 
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use action::Action;
+use config::Settings;
+use input::{BadResult, GoodResult, AUTOSAVE_SLOT, IRONMAN_SLOT};
+use keymap::Keymap;
+use replay::Replay;
+use world::{GameMode, World};
+use world_map::WorldDef;
+
 fn main() {
-    title::draw(2, 2);
-    cod::read::key();
+    let args = match cli::Args::parse() {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("{msg}");
+            std::process::exit(2);
+        }
+    };
 
-    let mut world = world::World::new(12, 14);
+    if let Err(e) = term::init() {
+        eprintln!("Failed to listen for terminal resizes: {e}");
+        std::process::exit(1);
+    }
 
-    loop {
-        cod::clear::all();
-        world.draw(0, 0);
+    if let Some(name) = &args.theme {
+        match theme::load(name) {
+            Ok(loaded) => theme::set(loaded),
+            Err(e) => {
+                eprintln!("Failed to load theme: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
 
-        cod::color::de();
-        cod::goto::bot();
+    if let Some(path) = &args.replay {
+        let replay = match Replay::load_from(path) {
+            Ok(replay) => replay,
+            Err(e) => {
+                eprintln!("Failed to load replay: {e}");
+                std::process::exit(1);
+            }
+        };
+
+        playback(replay, args.speed);
+        return;
+    }
+
+    let mut settings = match Settings::load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Failed to load settings: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    palette::set_colorblind(settings.colorblind);
+
+    let mut keymap = match Keymap::load() {
+        Ok(keymap) => keymap,
+        Err(e) => {
+            eprintln!("Failed to load key bindings: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let def = load_world(args.world.as_deref());
+
+    let Some(mut world) = start_menu(&def, &args, &mut settings, &mut keymap) else {
+        cod::clear::all();
+        cod::goto::home();
         cod::flush();
+        return;
+    };
+
+    // a crash is when a replay is most useful, so it's saved even then
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        play(&mut world, &settings, &mut keymap)
+    }));
+
+    if let Some(replay) = &world.recording {
+        let path = args.record.clone().unwrap_or_else(replay::default_path);
+        if let Err(e) = replay.save_to(path) {
+            world.draw_message(format!("Failed to save replay: {e}"), 1);
+            term::key();
+        }
+    }
+
+    let res = match res {
+        Ok(res) => res,
+        Err(panic) => panic::resume_unwind(panic),
+    };
+
+    // a dead ironman run stays dead
+    if world.mode == GameMode::Ironman && res != BadResult::Quit {
+        let _ = save::delete(&save::slot_path(IRONMAN_SLOT));
+    }
+
+    death(&mut world, res);
+}
+
+/// Runs the game until the player dies or quits.
+fn play(world: &mut World, settings: &Settings, keymap: &mut Keymap) -> BadResult {
+    // loading an ironman save deletes it, so it has to be written back
+    // before anything else can end the run
+    if world.mode == GameMode::Ironman {
+        slots::save_quick(world, IRONMAN_SLOT, 0);
+    }
+
+    let mut events = Vec::new();
+    let mut last_autosave = 0;
+    loop {
+        if !draw_frame(world, events, true) {
+            // nothing's shown, so keys aren't acted on until it fits
+            term::key();
+            events = Vec::new();
+            continue;
+        }
+
+        let sector = world.map.sector().id.clone();
+        let turn = world.turn;
+        match input::handle(world, settings, keymap) {
+            Ok(new_events) => events = new_events,
+            Err(res) => break res,
+        }
+
+        // the turn count went back to whatever the save was on
+        if events.contains(&GoodResult::Loaded) {
+            last_autosave = world.turn;
+        }
+
+        if world.mode == GameMode::Ironman {
+            if world.turn != turn {
+                slots::save_quick(world, IRONMAN_SLOT, 0);
+            }
+            continue;
+        }
 
-        let res = world.update();
-        if let Err(res) = res {
+        let interval = settings.autosave_interval;
+        let due = interval != 0 && world.turn >= last_autosave + interval;
+        if (due || world.map.sector().id != sector)
+            && slots::save_quick(world, AUTOSAVE_SLOT, settings.backups)
+        {
+            last_autosave = world.turn;
+            events.push(GoodResult::Autosaved);
+        }
+    }
+}
+
+fn load_world(path: Option<&Path>) -> WorldDef {
+    let def = match path {
+        Some(path) => world_map::load(path),
+        None => world_map::builtin(),
+    };
+
+    match def {
+        Ok(def) => def,
+        Err(e) => {
+            eprintln!("Failed to load world: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Runs the start menu until a game is started (or loaded). Returns
+/// `None` if the player quits instead.
+fn start_menu(
+    def: &WorldDef,
+    args: &cli::Args,
+    settings: &mut Settings,
+    keymap: &mut Keymap,
+) -> Option<World> {
+    loop {
+        let latest = slots::list(false).into_iter().find(|s| s.info.is_ok());
+
+        match menu::start(latest.is_some(), keymap) {
+            menu::Choice::NewGame => {
+                let Some(options) = menu::new_game(args.seed(), keymap) else {
+                    continue;
+                };
+
+                let mut world = World::new(def.clone(), options.seed);
+                world.mode = options.mode;
+                world.diagonal = options.diagonal;
+                world.recording = Some(Replay::new(
+                    options.seed,
+                    args.world.clone(),
+                    options.diagonal,
+                ));
+                // goes through `update` so the replay picks it up
+                let _ = world.update(Action::SetDifficulty(options.difficulty));
+                return Some(world);
+            }
+            menu::Choice::Continue => {
+                let mut world = World::new(def.clone(), args.seed());
+                if latest.is_some_and(|slot| save::load_from(slot.path, &mut world)) {
+                    return Some(world);
+                }
+            }
+            menu::Choice::Load => {
+                let mut world = World::new(def.clone(), args.seed());
+                if slots::load(&mut world) {
+                    return Some(world);
+                }
+            }
+            menu::Choice::Settings => menu::settings(settings, keymap),
+            menu::Choice::Quit => return None,
+        }
+    }
+}
+
+/// Logs the results of the last turn and redraws the world. If `wait`
+/// is set, dialogue stays up until a key is pressed. Returns false if
+/// the terminal is too small to draw the world in.
+fn draw_frame(world: &mut World, events: Vec<GoodResult>, wait: bool) -> bool {
+    for event in &events {
+        world.log_result(event);
+    }
+
+    render::clear();
+
+    let (width, height) = render::size();
+    let (min_width, min_height) = world::MIN_SIZE;
+    if width < min_width || height < min_height {
+        render::fg(1);
+        render::blit(
+            format!(
+                "The terminal is too small ({width}x{height}).\n\
+                 It needs to be at least {min_width}x{min_height}."
+            ),
+            0,
+            0,
+        );
+        render::flush();
+        return false;
+    }
+
+    world.draw(0, 0);
+    for event in events {
+        let GoodResult::Dialogue(speech) = event else {
+            continue;
+        };
+        world.draw_dialogue(&speech);
+
+        if wait {
+            term::key();
+            render::clear();
             world.draw(0, 0);
-            world.draw_message(quip::random(res), 1);
-            break;
         }
     }
 
+    render::flush();
+    true
+}
+
+/// Plays a replay back, waiting `speed` milliseconds between actions
+/// (or for a keypress, if `speed` is zero).
+fn playback(replay: Replay, speed: u64) {
+    let def = load_world(replay.world.as_deref());
+    let mut world = World::new(def, replay.seed);
+    world.diagonal = replay.diagonal;
+
+    let mut events = Vec::new();
+    for (turn, action) in replay.actions {
+        draw_frame(&mut world, events, false);
+
+        if speed == 0 {
+            term::key();
+        } else {
+            thread::sleep(Duration::from_millis(speed));
+        }
+
+        if turn != world.turn {
+            world.draw_message(format!("Replay desynced on turn {turn}"), 1);
+            finish(&world);
+            return;
+        }
+
+        match world.update(action) {
+            Ok(new_events) => events = new_events,
+            Err(res) => {
+                death(&mut world, res);
+                return;
+            }
+        }
+    }
+
+    draw_frame(&mut world, events, false);
+    world.draw_message("End of replay", 3);
+    finish(&world);
+}
+
+fn death(world: &mut World, res: BadResult) {
+    render::clear();
+    world.draw(0, 0);
+    let quip = quip::random(res, &mut world.rng);
+    world.draw_message(quip, 1);
+    finish(world);
+}
+
+fn finish(world: &World) {
     cod::goto::bot();
-    cod::goto::up(1);
+    cod::goto::up(2);
+    cod::color::de();
+    println!("   Seed: {}", world.seed);
     println!("   Press any key to exit   ");
-    cod::read::key();
+    term::key();
 }
@@ -1,53 +1,86 @@
 use std::collections::HashMap;
 
-use rand::distributions::{Distribution, Standard, Uniform};
+use rand::distributions::{Distribution, Uniform};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::entity::Entity;
+use crate::palette;
+use crate::render;
 use crate::sector::Sector;
 pub use crate::sector::{HEIGHT, WIDTH};
+use crate::theme;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(usize)]
 pub enum Direction {
     Up,
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
 impl Direction {
+    pub const CARDINAL: [Direction; 4] = [Self::Up, Self::Down, Self::Left, Self::Right];
+    pub const ALL: [Direction; 8] = [
+        Self::Up,
+        Self::Down,
+        Self::Left,
+        Self::Right,
+        Self::UpLeft,
+        Self::UpRight,
+        Self::DownLeft,
+        Self::DownRight,
+    ];
+
     pub fn diff(&self) -> (i32, i32) {
         match self {
             Self::Up => (0, -1),
             Self::Down => (0, 1),
             Self::Left => (-1, 0),
             Self::Right => (1, 0),
+            Self::UpLeft => (-1, -1),
+            Self::UpRight => (1, -1),
+            Self::DownLeft => (-1, 1),
+            Self::DownRight => (1, 1),
         }
     }
-}
 
-impl Distribution<Direction> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Direction {
-        match Uniform::new(0, 4).sample(rng) {
-            0 => Direction::Up,
-            1 => Direction::Down,
-            2 => Direction::Left,
-            3 => Direction::Right,
-            _ => unreachable!(),
-        }
+    /// The direction moving by `(x, y)` goes in, going by sign alone.
+    pub fn from_diff(x: i32, y: i32) -> Option<Self> {
+        let diff = (x.signum(), y.signum());
+        Self::ALL.into_iter().find(|d| d.diff() == diff)
+    }
+
+    pub fn is_diagonal(&self) -> bool {
+        let (x, y) = self.diff();
+        x != 0 && y != 0
+    }
+
+    /// Picks a random direction, only including diagonals if `diagonal`
+    /// is set.
+    pub fn random(rng: &mut impl Rng, diagonal: bool) -> Self {
+        let directions: &[Direction] = if diagonal {
+            &Self::ALL
+        } else {
+            &Self::CARDINAL
+        };
+        directions[Uniform::new(0, directions.len() as u32).sample(rng) as usize]
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Map {
-    pub sectors: HashMap<&'static str, Sector>,
+    pub sectors: HashMap<String, Sector>,
     pub current_sector: Sector,
 }
 
 impl Map {
-    pub fn new(sectors: HashMap<&'static str, Sector>, start: &str) -> (Vec<Entity>, Self) {
+    pub fn new(sectors: HashMap<String, Sector>, start: &str) -> (Vec<Entity>, Self) {
         let current_sector = sectors
             .get(start)
             .expect("Tried to initialize map with invalid start sector")
@@ -65,19 +98,17 @@ impl Map {
         let new_sector = self
             .sectors
             .get(id)
-            .expect("Found invalid sector identifier")
+            .unwrap_or_else(|| panic!("Found invalid sector identifier `{id}`"))
             .clone();
         let old_sector = std::mem::replace(&mut self.current_sector, new_sector);
-        self.sectors.insert(old_sector.id, old_sector);
+        self.sectors.insert(old_sector.id.clone(), old_sector);
 
         self.current_sector.entities().to_vec()
     }
 
     pub fn save_entities(&mut self, id: &str, entities: Vec<Entity>) {
-        self.sectors
-            .get_mut(id)
-            .expect("Found invalid sector identifier")
-            .clone()
+        self.get_sector_mut(id)
+            .unwrap_or_else(|| panic!("Found invalid sector identifier `{id}`"))
             .save_entities(entities);
     }
 
@@ -93,7 +124,6 @@ impl Map {
         self.sector().tiles()
     }
 
-    #[allow(dead_code)]
     pub fn get_sector(&self, id: &str) -> Option<&Sector> {
         if id == self.current_sector.id {
             Some(&self.current_sector)
@@ -125,20 +155,20 @@ impl Map {
             for tile in row {
                 dark = !dark;
                 tile.draw(x, y, dark);
-                cod::color::de_bg();
+                render::de_bg();
                 x += 2;
             }
 
-            if WIDTH % 2 == 0 {
+            if WIDTH.is_multiple_of(2) {
                 dark = !dark
             };
             x = ox;
             y += 1;
 
-            cod::color::de_fg();
-            print!("|");
+            render::de_fg();
+            render::print("|");
         }
-        println!("\n{}/", "-".repeat(WIDTH * 2));
+        render::print(format!("\n{}/", "-".repeat(WIDTH * 2)));
     }
 }
 
@@ -157,7 +187,37 @@ pub enum TileKind {
 }
 
 impl TileKind {
+    /// Whether wandering entities can walk on the tile.
+    pub fn walkable(&self) -> bool {
+        !matches!(
+            self,
+            Self::Water | Self::Mountain | Self::Village | Self::Building
+        )
+    }
+
+    pub fn from_char(ch: char) -> Option<Self> {
+        Some(match ch {
+            '~' => Self::Water,
+            '_' => Self::Grass,
+            '$' => Self::Forest,
+            'n' => Self::Hill,
+            'A' => Self::Mountain,
+            '=' => Self::Road,
+            '%' => Self::Village,
+            '^' => Self::Building,
+            _ => None?,
+        })
+    }
+
     pub fn color(&self) -> (u8, u8, u8) {
+        // the colorblind palette takes priority over the theme's
+        if palette::colorblind() {
+            return self.colorblind_color();
+        }
+        if let Some(color) = theme::get().tile_color(*self) {
+            return color;
+        }
+
         match self {
             Self::Water => (0, 77, 153),
             Self::Grass => (0, 153, 25),
@@ -169,6 +229,20 @@ impl TileKind {
         }
     }
 
+    /// Tells grass, forest and buildings apart by more than how red
+    /// or green they are.
+    fn colorblind_color(&self) -> (u8, u8, u8) {
+        match self {
+            Self::Water => (0, 114, 178),
+            Self::Grass => (0, 158, 115),
+            Self::Forest => (0, 80, 100),
+            Self::Hill => (255, 238, 230),
+            Self::Mountain => (230, 255, 242),
+            Self::Road => (158, 158, 158),
+            Self::Village | Self::Building => (213, 94, 0),
+        }
+    }
+
     pub fn faded_color(&self) -> (u8, u8, u8) {
         let (mut r, mut g, mut b) = self.color();
 
@@ -205,16 +279,13 @@ pub struct Tile {
 
 impl Tile {
     pub fn draw(&self, x: u32, y: u32, dark_bg: bool) {
-        let (r, g, b) = self.kind.color();
-        cod::color::tc_fg(r, g, b);
-
-        let (r, g, b) = if dark_bg {
+        render::fg(self.kind.color());
+        render::bg(if dark_bg {
             self.kind.dark_faded_color()
         } else {
             self.kind.faded_color()
-        };
-        cod::color::tc_bg(r, g, b);
+        });
 
-        cod::blit(format!("{0}{0}", self.kind as u8 as char), x, y);
+        render::blit(theme::get().tile_glyph(self.kind), x, y);
     }
 }
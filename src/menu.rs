@@ -0,0 +1,455 @@
+use cod::Key;
+
+use crate::config::Settings;
+use crate::difficulty::Difficulty;
+use crate::keymap::{self, Command, Keymap, PRESETS};
+use crate::palette;
+use crate::render;
+use crate::term;
+use crate::title;
+use crate::world::GameMode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    NewGame,
+    Continue,
+    Load,
+    Settings,
+    Quit,
+}
+
+const CHOICES: [(&str, Choice); 5] = [
+    ("New game", Choice::NewGame),
+    ("Continue", Choice::Continue),
+    ("Load", Choice::Load),
+    ("Settings", Choice::Settings),
+    ("Quit", Choice::Quit),
+];
+
+/// Shows the start menu below the title. `Continue` can only be picked
+/// if `can_continue` is set.
+pub fn start(can_continue: bool, keymap: &Keymap) -> Choice {
+    let enabled = |choice| choice != Choice::Continue || can_continue;
+    let entries: Vec<_> = CHOICES
+        .iter()
+        .map(|(name, choice)| (*name, enabled(*choice)))
+        .collect();
+
+    let mut selected = 0;
+    loop {
+        title::draw(2, 2, &entries, selected);
+
+        let Some(key) = term::key() else {
+            continue;
+        };
+        let key = menu_key(key, keymap);
+
+        match key {
+            Key::ArrowUp => selected = selected.saturating_sub(1),
+            Key::ArrowDown => selected = (selected + 1).min(CHOICES.len() - 1),
+            Key::Enter if entries[selected].1 => return CHOICES[selected].1,
+            Key::Char('q' | 'Q') | Key::Escape => return Choice::Quit,
+            _ => {}
+        }
+
+        // skip over disabled entries
+        if !entries[selected].1 {
+            selected = match key {
+                Key::ArrowUp => selected.saturating_sub(1),
+                _ => selected + 1,
+            };
+        }
+    }
+}
+
+/// The choices made when starting a new game.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NewGame {
+    pub difficulty: Difficulty,
+    pub seed: u64,
+    pub mode: GameMode,
+    pub diagonal: bool,
+}
+
+const DIFFICULTIES: [&str; 3] = ["easy", "normal", "hard"];
+const MODES: [GameMode; 2] = [GameMode::Normal, GameMode::Ironman];
+
+/// Lets the player set up a new game, starting from `seed`. Returns
+/// `None` if they back out.
+pub fn new_game(seed: u64, keymap: &Keymap) -> Option<NewGame> {
+    let mut options = NewGame {
+        difficulty: Difficulty::normal(),
+        seed,
+        mode: GameMode::Normal,
+        diagonal: false,
+    };
+
+    // difficulty, seed, mode, movement, then "Start"
+    let mut selected = 4;
+    loop {
+        let about = match options.mode {
+            GameMode::Normal => "Save and load whenever you like",
+            GameMode::Ironman => "Saved every turn; death is permanent",
+        };
+        let rows = [
+            (format!("Difficulty: < {} >", options.difficulty.name()), ""),
+            (format!("Seed: {}", options.seed), "Enter to change"),
+            (format!("Mode: < {} >", options.mode.name()), about),
+            (
+                format!(
+                    "Movement: < {} >",
+                    if options.diagonal { "8-way" } else { "4-way" }
+                ),
+                "",
+            ),
+        ];
+        let bottom = draw_form(
+            "- New game -+",
+            &rows,
+            "[Start]",
+            selected,
+            "Left/Right: change  Enter: select  q: back",
+        );
+
+        let Some(key) = term::key() else {
+            continue;
+        };
+        let key = menu_key(key, keymap);
+
+        let step = match key {
+            Key::ArrowLeft => -1,
+            Key::ArrowRight => 1,
+            _ => 0,
+        };
+
+        match (key, selected) {
+            (Key::ArrowUp, _) => selected = selected.saturating_sub(1),
+            (Key::ArrowDown, _) => selected = (selected + 1).min(rows.len()),
+            (Key::ArrowLeft | Key::ArrowRight, 0) => {
+                let name = options.difficulty.name();
+                let i = DIFFICULTIES.iter().position(|d| *d == name).unwrap_or(1);
+                let name = DIFFICULTIES[cycle(i, step, DIFFICULTIES.len())];
+                options.difficulty = Difficulty::from_name(name).unwrap();
+            }
+            (Key::Enter, 1) => {
+                while let Some(seed) = prompt("Seed (blank for random): ", bottom) {
+                    if seed.is_empty() {
+                        options.seed = rand::random();
+                        break;
+                    }
+                    match seed.parse() {
+                        Ok(seed) => {
+                            options.seed = seed;
+                            break;
+                        }
+                        Err(_) => message("Seed must be a number", bottom),
+                    }
+                }
+            }
+            (Key::ArrowLeft | Key::ArrowRight, 2) => {
+                let i = MODES.iter().position(|m| *m == options.mode).unwrap_or(0);
+                options.mode = MODES[cycle(i, step, MODES.len())];
+            }
+            (Key::ArrowLeft | Key::ArrowRight, 3) => options.diagonal = !options.diagonal,
+            (Key::Enter, 4) => return Some(options),
+            (Key::Char('q' | 'Q') | Key::Escape, _) => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Lets the player change their settings, saving them on the way out.
+pub fn settings(settings: &mut Settings, keymap: &mut Keymap) {
+    let mut selected = 0;
+    loop {
+        let autosave = match settings.autosave_interval {
+            0 => "off".to_string(),
+            n => format!("every {n} turns"),
+        };
+        let rows = [
+            (format!("Autosave: < {autosave} >"), ""),
+            (
+                format!("Backups: < {} >", settings.backups),
+                "Older copies kept per slot",
+            ),
+            (
+                format!(
+                    "Colorblind palette: < {} >",
+                    if settings.colorblind { "on" } else { "off" }
+                ),
+                "Oranges and blues instead of reds and greens",
+            ),
+            ("Key bindings".to_string(), "Enter to change"),
+        ];
+        let bottom = draw_form(
+            "- Settings -+",
+            &rows,
+            "[Back]",
+            selected,
+            "Left/Right: change  Enter/q: back",
+        );
+
+        let Some(key) = term::key() else {
+            continue;
+        };
+        let key = menu_key(key, keymap);
+
+        match (key, selected) {
+            (Key::ArrowUp, _) => selected = selected.saturating_sub(1),
+            (Key::ArrowDown, _) => selected = (selected + 1).min(rows.len()),
+            (Key::ArrowLeft, 0) => {
+                settings.autosave_interval = settings.autosave_interval.saturating_sub(5)
+            }
+            (Key::ArrowRight, 0) => settings.autosave_interval += 5,
+            (Key::ArrowLeft, 1) => settings.backups = settings.backups.saturating_sub(1),
+            (Key::ArrowRight, 1) => settings.backups += 1,
+            (Key::ArrowLeft | Key::ArrowRight, 2) => {
+                settings.colorblind = !settings.colorblind;
+                palette::set_colorblind(settings.colorblind);
+            }
+            (Key::Enter, 3) => key_bindings(keymap),
+            (Key::Enter, 4) | (Key::Char('q' | 'Q') | Key::Escape, _) => {
+                if let Err(e) = settings.save() {
+                    message(&e.to_string(), bottom);
+                }
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Lets the player rebind keys or switch to a preset, saving the
+/// keymap on the way out.
+pub fn key_bindings(keymap: &mut Keymap) {
+    let mut preset = 0;
+    // 0 is the preset, then one row per command
+    let mut selected = 1;
+
+    loop {
+        let bottom = draw_key_bindings(keymap, PRESETS[preset], selected);
+
+        let Some(key) = term::key() else {
+            continue;
+        };
+        let key = menu_key(key, keymap);
+
+        let command = selected.checked_sub(1).map(|i| Command::ALL[i]);
+        match (key, command) {
+            (Key::ArrowUp, _) => selected = selected.saturating_sub(1),
+            (Key::ArrowDown, _) => selected = (selected + 1).min(Command::ALL.len()),
+            (Key::ArrowLeft, None) => preset = cycle(preset, -1, PRESETS.len()),
+            (Key::ArrowRight, None) => preset = cycle(preset, 1, PRESETS.len()),
+            (Key::Enter, None) => *keymap = Keymap::preset(PRESETS[preset]).unwrap(),
+
+            (Key::Enter, Some(command)) => {
+                let msg = format!("Press a key for `{}` (Esc to cancel)", command.name());
+                let Some(key) = read_key(&msg, bottom) else {
+                    continue;
+                };
+                // it couldn't be written to the config file
+                let Some(name) = keymap::key_name(&key) else {
+                    message("That key can't be bound, try another", bottom);
+                    continue;
+                };
+
+                match keymap.command(&key) {
+                    Some(other) if other == command => {}
+                    Some(other)
+                        if Command::REQUIRED.contains(&other) && keymap.keys(other).len() == 1 =>
+                    {
+                        let msg = format!("`{name}` is the only key for `{}`", other.name());
+                        message(&msg, bottom);
+                    }
+                    Some(other) => {
+                        let msg =
+                            format!("`{name}` is bound to `{}`. Rebind it (y/N)?", other.name());
+                        if confirm(&msg, bottom) {
+                            keymap.bind(command, key);
+                        }
+                    }
+                    None => keymap.bind(command, key),
+                }
+            }
+            (Key::Char('x' | 'X') | Key::Del | Key::Backspace, Some(command)) => {
+                if Command::REQUIRED.contains(&command) {
+                    let msg = format!("`{}` can't be left without a key", command.name());
+                    message(&msg, bottom);
+                } else {
+                    keymap.clear(command)
+                }
+            }
+
+            (Key::Char('q' | 'Q') | Key::Escape, _) => {
+                if let Err(e) = keymap.save() {
+                    message(&e.to_string(), bottom);
+                }
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draw_key_bindings(keymap: &Keymap, preset: &str, selected: usize) -> u32 {
+    cod::clear::all();
+    render::invalidate();
+    cod::color::de();
+
+    cod::pixel('+', 0, 0);
+    cod::blit("- Key bindings -+", 1, 0);
+
+    cod::blit(format!("Preset: < {preset} >"), 3, 1);
+    palette::fg(8);
+    cod::blit("Enter to apply", 5, 2);
+    cod::color::de_fg();
+
+    let mut y = 3;
+    for command in Command::ALL {
+        y += 1;
+        cod::blit(command.name(), 3, y);
+
+        let keys = keymap.keys(command);
+        if keys.is_empty() {
+            palette::fg(1);
+            cod::blit("unbound", 18, y);
+        } else {
+            palette::fg(3);
+            let keys: Vec<_> = keys.iter().filter_map(keymap::key_name).collect();
+            cod::blit(keys.join(", "), 18, y);
+        }
+        cod::color::de_fg();
+    }
+
+    let marker = if selected == 0 {
+        1
+    } else {
+        3 + selected as u32
+    };
+    cod::pixel('-', 1, marker);
+
+    cod::orth_line('|', 0, 1, 0, y).unwrap();
+    cod::pixel('+', 0, y + 1);
+
+    palette::fg(8);
+    cod::blit(
+        "Enter: bind  x: unbind  Left/Right: preset  q: back",
+        2,
+        y + 3,
+    );
+    cod::color::de();
+
+    cod::goto::bot();
+    cod::flush();
+
+    y + 4
+}
+
+/// Turns the player's own movement and quit keys into the arrows and
+/// Escape, so menus can be moved through the same way as the game.
+fn menu_key(key: Key, keymap: &Keymap) -> Key {
+    match keymap.command(&key) {
+        Some(Command::Up) => Key::ArrowUp,
+        Some(Command::Down) => Key::ArrowDown,
+        Some(Command::Left) => Key::ArrowLeft,
+        Some(Command::Right) => Key::ArrowRight,
+        Some(Command::Quit) => Key::Escape,
+        _ => key,
+    }
+}
+
+fn cycle(i: usize, step: isize, len: usize) -> usize {
+    (i as isize + step).rem_euclid(len as isize) as usize
+}
+
+/// Draws a menu of option rows (each with a greyed-out description
+/// below it) followed by a button. Returns the first free line below it.
+fn draw_form(
+    title: &str,
+    rows: &[(String, &str)],
+    button: &str,
+    selected: usize,
+    help: &str,
+) -> u32 {
+    cod::clear::all();
+    render::invalidate();
+    cod::color::de();
+
+    cod::pixel('+', 0, 0);
+    cod::blit(title, 1, 0);
+
+    let mut y = 1;
+    for (i, (row, about)) in rows.iter().enumerate() {
+        if i == selected {
+            cod::pixel('-', 1, y);
+        }
+
+        cod::blit(row, 3, y);
+        palette::fg(8);
+        cod::blit(*about, 5, y + 1);
+        cod::color::de_fg();
+        y += 2;
+    }
+
+    if selected == rows.len() {
+        cod::pixel('-', 1, y);
+    }
+    palette::fg(2);
+    cod::blit(button, 3, y);
+    cod::color::de_fg();
+
+    cod::orth_line('|', 0, 1, 0, y).unwrap();
+    cod::pixel('+', 0, y + 1);
+
+    palette::fg(8);
+    cod::blit(help, 2, y + 3);
+    cod::color::de();
+
+    cod::goto::bot();
+    cod::flush();
+
+    y + 4
+}
+
+fn prompt(msg: &str, y: u32) -> Option<String> {
+    cod::goto::pos(0, y);
+    cod::clear::line();
+    palette::fg(2);
+    print!("{msg}");
+    cod::color::de_fg();
+    cod::flush();
+
+    Some(term::line()?.trim().to_string())
+}
+
+fn read_key(msg: &str, y: u32) -> Option<Key> {
+    cod::goto::pos(0, y);
+    cod::clear::line();
+    palette::fg(2);
+    print!("{msg}");
+    cod::color::de_fg();
+    cod::flush();
+
+    term::key().filter(|key| *key != Key::Escape)
+}
+
+fn confirm(msg: &str, y: u32) -> bool {
+    cod::goto::pos(0, y);
+    cod::clear::line();
+    palette::fg(1);
+    print!("{msg}");
+    cod::color::de_fg();
+    cod::flush();
+
+    matches!(term::key(), Some(Key::Char('y' | 'Y')))
+}
+
+fn message(msg: &str, y: u32) {
+    cod::goto::pos(0, y);
+    cod::clear::line();
+    palette::fg(1);
+    print!("{msg}");
+    cod::color::de_fg();
+    cod::flush();
+    term::key();
+}
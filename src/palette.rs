@@ -0,0 +1,193 @@
+use std::cell::Cell;
+use std::env;
+
+use crate::render::Color;
+
+/// How many colors the terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    /// No color at all, e.g. when `NO_COLOR` is set.
+    Mono,
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl Depth {
+    /// Works out what the terminal supports from `NO_COLOR`, `COLORTERM`
+    /// and `TERM`.
+    pub fn detect() -> Self {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+
+        if no_color || term.is_empty() || term == "dumb" {
+            Self::Mono
+        } else if matches!(colorterm.as_str(), "truecolor" | "24bit")
+            || term.contains("truecolor")
+            || term.contains("direct")
+        {
+            Self::TrueColor
+        } else if term.contains("256") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Palette {
+    depth: Depth,
+    /// Swaps reds and greens for colors that are easier to tell apart.
+    colorblind: bool,
+}
+
+thread_local! {
+    static PALETTE: Cell<Palette> = Cell::new(Palette {
+        depth: Depth::detect(),
+        colorblind: false,
+    });
+}
+
+pub fn colorblind() -> bool {
+    PALETTE.get().colorblind
+}
+
+pub fn set_colorblind(colorblind: bool) {
+    PALETTE.set(Palette {
+        colorblind,
+        ..PALETTE.get()
+    });
+}
+
+/// Sets the foreground color, as close as the terminal can show it.
+pub fn fg(color: impl Into<Color>) {
+    match map(color.into(), true) {
+        Color::Default => cod::color::de_fg(),
+        Color::Indexed(n) if n < 16 => sgr(if n < 8 { 30 + n } else { 90 + n - 8 }),
+        Color::Indexed(n) => cod::color::fg(n),
+        Color::Rgb(r, g, b) => cod::color::tc_fg(r, g, b),
+    }
+}
+
+/// Sets the background color, as close as the terminal can show it.
+pub fn bg(color: impl Into<Color>) {
+    match map(color.into(), false) {
+        Color::Default => cod::color::de_bg(),
+        Color::Indexed(n) if n < 16 => sgr(if n < 8 { 40 + n } else { 100 + n - 8 }),
+        Color::Indexed(n) => cod::color::bg(n),
+        Color::Rgb(r, g, b) => cod::color::tc_bg(r, g, b),
+    }
+}
+
+/// The basic 16 colors are set with their own codes, since 16-color
+/// terminals don't all understand the 256-color ones.
+fn sgr(code: u8) {
+    print!("\x1b[{code}m");
+}
+
+/// Maps a color to the closest one the terminal can show.
+fn map(color: Color, fg: bool) -> Color {
+    let palette = PALETTE.get();
+    let color = if palette.colorblind {
+        colorblind_color(color)
+    } else {
+        color
+    };
+
+    match (palette.depth, color) {
+        (Depth::Mono, _) | (_, Color::Default) => Color::Default,
+        (Depth::TrueColor, _) => color,
+        (Depth::Ansi256, Color::Rgb(r, g, b)) => Color::Indexed(nearest_256((r, g, b))),
+        (Depth::Ansi256, _) => color,
+        (Depth::Ansi16, Color::Indexed(0..16)) => color,
+        (Depth::Ansi16, Color::Rgb(r, g, b)) => Color::Indexed(nearest_16((r, g, b), fg)),
+        (Depth::Ansi16, Color::Indexed(n)) => Color::Indexed(nearest_16(rgb_256(n), fg)),
+    }
+}
+
+/// Swaps the indexed reds for oranges and greens for blues. Truecolor
+/// tiles have a palette of their own (see `TileKind::color`).
+fn colorblind_color(color: Color) -> Color {
+    match color {
+        Color::Indexed(1 | 160 | 196) => Color::Indexed(202),
+        Color::Indexed(9) => Color::Indexed(208),
+        Color::Indexed(2 | 28 | 34) => Color::Indexed(33),
+        Color::Indexed(10 | 22 | 40 | 46) => Color::Indexed(39),
+        _ => color,
+    }
+}
+
+/// The standard xterm values of the basic 16 colors.
+const ANSI_16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The levels each channel of the 6x6x6 color cube can be.
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// What a 256-color index looks like in 24-bit color.
+fn rgb_256(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..16 => ANSI_16[n as usize],
+        16..232 => {
+            let n = n - 16;
+            (
+                CUBE[n as usize / 36],
+                CUBE[n as usize / 6 % 6],
+                CUBE[n as usize % 6],
+            )
+        }
+        _ => {
+            let v = 8 + (n - 232) * 10;
+            (v, v, v)
+        }
+    }
+}
+
+/// The closest color in the color cube or grayscale ramp. The basic 16
+/// are skipped since terminals often change them.
+fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+    (16..=255)
+        .min_by_key(|&n| distance(rgb, rgb_256(n)))
+        .unwrap()
+}
+
+/// The closest of the basic 16 colors. Dark foregrounds would mostly
+/// come out black, which doesn't show up on the dark backgrounds, so for
+/// those only the hue is matched.
+fn nearest_16(rgb: (u8, u8, u8), fg: bool) -> u8 {
+    let (r, g, b) = rgb;
+    let (rgb, first) = if fg {
+        let max = r.max(g).max(b).max(1) as u32;
+        let scale = |c: u8| (c as u32 * 205 / max) as u8;
+        ((scale(r), scale(g), scale(b)), 1)
+    } else {
+        (rgb, 0)
+    };
+
+    (first..16)
+        .min_by_key(|&n| distance(rgb, ANSI_16[n as usize]))
+        .unwrap()
+}
@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+
+use crate::map::{Direction, Map, HEIGHT, WIDTH};
+
+/// How many moves it takes to get to each tile, or `None` if it can't
+/// be reached.
+pub type Distances = [[Option<u32>; WIDTH]; HEIGHT];
+
+/// Does a breadth-first search outwards from `from` over walkable tiles,
+/// skipping any tile `blocked` returns true for.
+pub fn distances(
+    map: &Map,
+    from: (u32, u32),
+    diagonal: bool,
+    blocked: impl Fn(u32, u32) -> bool,
+) -> Distances {
+    let mut dist = [[None; WIDTH]; HEIGHT];
+    dist[from.1 as usize][from.0 as usize] = Some(0);
+
+    let mut queue = VecDeque::from([from]);
+    while let Some((x, y)) = queue.pop_front() {
+        let d = dist[y as usize][x as usize].unwrap();
+
+        for (nx, ny) in neighbors(map, x, y, diagonal) {
+            if dist[ny as usize][nx as usize].is_none() && !blocked(nx, ny) {
+                dist[ny as usize][nx as usize] = Some(d + 1);
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+
+    dist
+}
+
+pub fn get(dist: &Distances, x: u32, y: u32) -> Option<u32> {
+    dist.get(y as usize)?.get(x as usize).copied().flatten()
+}
+
+/// The walkable tiles one move away from `(x, y)`.
+pub fn neighbors(
+    map: &Map,
+    x: u32,
+    y: u32,
+    diagonal: bool,
+) -> impl Iterator<Item = (u32, u32)> + '_ {
+    let directions: &[Direction] = if diagonal {
+        &Direction::ALL
+    } else {
+        &Direction::CARDINAL
+    };
+
+    directions.iter().filter_map(move |dir| {
+        let (dx, dy) = dir.diff();
+        let x = x.checked_add_signed(dx)?;
+        let y = y.checked_add_signed(dy)?;
+        map.get(x, y)
+            .filter(|tile| tile.kind.walkable())
+            .map(|_| (x, y))
+    })
+}
+
+/// The move from `(x, y)` that gets closest to wherever `dist` was
+/// measured from, if any gets closer at all.
+pub fn step_towards(
+    map: &Map,
+    dist: &Distances,
+    x: u32,
+    y: u32,
+    diagonal: bool,
+) -> Option<(u32, u32)> {
+    let here = get(dist, x, y).unwrap_or(u32::MAX);
+    neighbors(map, x, y, diagonal)
+        .filter_map(|(nx, ny)| Some((get(dist, nx, ny)?, (nx, ny))))
+        .filter(|(d, _)| *d < here)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, pos)| pos)
+}
+
+/// The move from `(x, y)` that gets furthest from wherever `dist` was
+/// measured from, if any gets further at all.
+pub fn step_away(
+    map: &Map,
+    dist: &Distances,
+    x: u32,
+    y: u32,
+    diagonal: bool,
+) -> Option<(u32, u32)> {
+    let here = get(dist, x, y)?;
+    neighbors(map, x, y, diagonal)
+        .filter_map(|(nx, ny)| Some((get(dist, nx, ny)?, (nx, ny))))
+        .filter(|(d, _)| *d > here)
+        .max_by_key(|(d, _)| *d)
+        .map(|(_, pos)| pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+    use crate::world_map;
+
+    /// The built-in start sector. (12, 14) is grass with water below it
+    /// and to its right, and grass above and to its left.
+    fn map() -> Map {
+        World::new(world_map::builtin().unwrap(), 0).map
+    }
+
+    #[test]
+    fn distances_count_moves() {
+        let map = map();
+
+        let dist = distances(&map, (12, 14), false, |_, _| false);
+        assert_eq!(get(&dist, 12, 14), Some(0));
+        assert_eq!(get(&dist, 12, 13), Some(1));
+        assert_eq!(get(&dist, 11, 13), Some(2));
+        assert_eq!(get(&dist, 12, 15), None);
+        assert_eq!(get(&dist, WIDTH as u32, 0), None);
+
+        let dist = distances(&map, (12, 14), true, |_, _| false);
+        assert_eq!(get(&dist, 11, 13), Some(1));
+    }
+
+    #[test]
+    fn blocked_tiles_are_walked_around() {
+        let map = map();
+
+        let dist = distances(&map, (12, 14), false, |x, y| (x, y) == (12, 13));
+        assert_eq!(get(&dist, 12, 13), None);
+        assert_eq!(get(&dist, 12, 12), Some(4));
+    }
+
+    #[test]
+    fn steps_go_towards_and_away() {
+        let map = map();
+        let dist = distances(&map, (12, 14), false, |_, _| false);
+
+        assert_eq!(step_towards(&map, &dist, 12, 12, false), Some((12, 13)));
+        assert_eq!(step_towards(&map, &dist, 12, 14, false), None);
+
+        let (x, y) = step_away(&map, &dist, 12, 13, false).unwrap();
+        assert_eq!(get(&dist, x, y), Some(2));
+    }
+}
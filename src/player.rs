@@ -1,6 +1,11 @@
+use std::collections::BTreeMap;
+
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-use crate::item::Item;
+use crate::item::{Item, Slot};
+use crate::status::{Status, StatusKind};
+use constants::*;
 
 pub mod constants {
     pub const HUNGER_INTERVAL: u32 = 8;
@@ -10,6 +15,12 @@ pub mod constants {
     pub const INITIAL_THIRST_CAP: u32 = 10;
 
     pub const UPGRADE_CHANCE: f32 = 0.4;
+    /// How many stacks of items can be carried, not counting what's
+    /// equipped. Gifts from NPCs are accepted even past the limit.
+    pub const CARRY_LIMIT: usize = 8;
+    /// How many tiles away the player can see entities.
+    pub const BASE_VISION: u32 = 10;
+    pub const WELL_FED_TURNS: u32 = 20;
     pub const CHARACTER: char = 'G';
 }
 
@@ -28,5 +39,325 @@ pub struct Player {
     pub max_health: u32,
     pub damage: u32,
 
+    /// Taken off the damage of every hit.
+    #[serde(default)]
+    pub armor: u32,
+    /// The percent chance of dodging a hit entirely.
+    #[serde(default)]
+    pub dodge: u32,
+    #[serde(default = "base_vision")]
+    pub vision: u32,
+    /// The percent chance of a move not taking up a turn.
+    #[serde(default)]
+    pub speed: u32,
+    /// Flat bonuses to each stat from items and statuses, added to the
+    /// values above. They're kept apart so that a debuff bigger than the
+    /// stat itself comes off again exactly.
+    #[serde(default)]
+    pub bonus: BTreeMap<Stat, i32>,
+    /// Percentage bonuses to each stat, applied after the flat ones.
+    #[serde(default)]
+    pub percent: BTreeMap<Stat, i32>,
+    #[serde(default)]
+    pub statuses: Vec<Status>,
+
     pub inventory: Vec<Item>,
+    /// What's worn in each slot, indexed by `Slot`.
+    #[serde(default)]
+    pub equipped: [Option<Item>; 3],
+}
+
+fn base_vision() -> u32 {
+    BASE_VISION
+}
+
+/// Something about the player that buffs can change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Stat {
+    MaxHealth,
+    Damage,
+    ThirstCap,
+    HungerCap,
+    Armor,
+    Dodge,
+    Vision,
+    Speed,
+}
+
+impl Stat {
+    pub const ALL: [Stat; 8] = [
+        Self::MaxHealth,
+        Self::Damage,
+        Self::ThirstCap,
+        Self::HungerCap,
+        Self::Armor,
+        Self::Dodge,
+        Self::Vision,
+        Self::Speed,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MaxHealth => "health",
+            Self::Damage => "damage",
+            Self::ThirstCap => "water",
+            Self::HungerCap => "food",
+            Self::Armor => "armor",
+            Self::Dodge => "dodge",
+            Self::Vision => "vision",
+            Self::Speed => "speed",
+        }
+    }
+
+    pub fn color(&self) -> u8 {
+        match self {
+            Self::MaxHealth => 1,
+            Self::Damage => 7,
+            Self::ThirstCap => 12,
+            Self::HungerCap => 223,
+            Self::Armor => 250,
+            Self::Dodge => 229,
+            Self::Vision => 183,
+            Self::Speed => 45,
+        }
+    }
+}
+
+impl Player {
+    /// The stat's value with every bonus applied, rounded to the nearest
+    /// whole number.
+    pub fn stat(&self, stat: Stat) -> u32 {
+        let base = match stat {
+            Stat::MaxHealth => self.max_health,
+            Stat::Damage => self.damage,
+            Stat::ThirstCap => self.thirst_cap,
+            Stat::HungerCap => self.hunger_cap,
+            Stat::Armor => self.armor,
+            Stat::Dodge => self.dodge,
+            Stat::Vision => self.vision,
+            Stat::Speed => self.speed,
+        };
+        let bonus = self.bonus.get(&stat).copied().unwrap_or(0);
+        let percent = 100 + self.percent.get(&stat).copied().unwrap_or(0);
+
+        let flat = (base as i64 + bonus as i64).max(0);
+        ((flat * percent.max(0) as i64 + 50) / 100) as u32
+    }
+
+    pub fn max_health(&self) -> u32 {
+        self.stat(Stat::MaxHealth)
+    }
+
+    pub fn damage(&self) -> u32 {
+        self.stat(Stat::Damage)
+    }
+
+    /// How much of a hit for `damage` the player takes after armor, or
+    /// `None` if they dodge it.
+    pub fn incoming(&self, damage: u32, rng: &mut impl Rng) -> Option<u32> {
+        let dodge = self.stat(Stat::Dodge);
+        if dodge > 0 && rng.gen_range(0..100) < dodge {
+            return None;
+        }
+
+        Some(self.mitigate(damage))
+    }
+
+    /// How much of a hit for `damage` gets through the player's armor.
+    /// Armor can't block a hit completely.
+    pub fn mitigate(&self, damage: u32) -> u32 {
+        damage
+            .saturating_sub(self.stat(Stat::Armor))
+            .max(damage.min(1))
+    }
+
+    /// Takes `food` off the player's hunger. Eating their fill leaves
+    /// them well-fed.
+    pub fn eat(&mut self, food: u32) {
+        self.hunger = self.hunger.saturating_sub(food);
+        if self.hunger == 0 {
+            self.add_status(StatusKind::WellFed, WELL_FED_TURNS);
+        }
+    }
+
+    pub fn heal(&mut self, amount: u32) {
+        self.health = (self.health + amount).min(self.max_health());
+    }
+
+    /// Starts a status effect. If one of the same kind is already
+    /// active, it's replaced and lasts for whichever is longer.
+    pub fn add_status(&mut self, kind: StatusKind, mut turns: u32) {
+        if let Some(i) = self.statuses.iter().position(|s| s.kind.same(&kind)) {
+            let old = self.statuses.remove(i);
+            old.kind.unapply(self);
+            turns = turns.max(old.turns);
+        }
+
+        kind.apply(self);
+        self.statuses.push(Status { kind, turns });
+    }
+
+    pub fn has_status(&self, kind: &StatusKind) -> bool {
+        self.statuses.iter().any(|s| s.kind.same(kind))
+    }
+
+    /// Ends the status at `i`, removing its buff.
+    pub fn end_status(&mut self, i: usize) -> Status {
+        let status = self.statuses.remove(i);
+        status.kind.unapply(self);
+        self.health = self.health.min(self.max_health());
+        status
+    }
+
+    /// Whether there's room for `item` in the inventory.
+    pub fn can_carry(&self, item: &Item) -> bool {
+        self.inventory.len() < CARRY_LIMIT || self.inventory.iter().any(|i| i.stacks_with(item))
+    }
+
+    /// Puts `item` in the inventory, stacking it onto a matching item if
+    /// there is one, and applies its buffs unless it has to be equipped
+    /// first.
+    pub fn add_item(&mut self, item: Item) {
+        if item.slot.is_none() {
+            for _ in 0..item.count {
+                item.apply(self);
+            }
+        }
+
+        match self.inventory.iter_mut().find(|i| i.stacks_with(&item)) {
+            Some(stack) => stack.count += item.count,
+            None => self.inventory.push(item),
+        }
+    }
+
+    /// Takes a single item off the stack at `i`, removing its buffs.
+    pub fn remove_item(&mut self, i: usize) -> Option<Item> {
+        let stack = self.inventory.get_mut(i)?;
+        let item = if stack.count > 1 {
+            stack.count -= 1;
+            let mut item = stack.clone();
+            item.count = 1;
+            if let Some(consumable) = &mut item.consumable {
+                consumable.used = 0;
+            }
+            item
+        } else {
+            self.inventory.remove(i)
+        };
+
+        if item.slot.is_none() {
+            item.unapply(self);
+        }
+        Some(item)
+    }
+
+    /// Whether an item called `name` is carried or equipped.
+    pub fn has_item(&self, name: &str) -> bool {
+        self.inventory
+            .iter()
+            .chain(self.equipped.iter().flatten())
+            .any(|i| i.name == name)
+    }
+
+    pub fn equipped(&self, slot: Slot) -> Option<&Item> {
+        self.equipped[slot as usize].as_ref()
+    }
+
+    /// Equips the item at `i` in its slot, putting whatever was there
+    /// back in the inventory. Returns false if the item has no slot.
+    pub fn equip(&mut self, i: usize) -> bool {
+        let Some(slot) = self.inventory.get(i).and_then(|item| item.slot) else {
+            return false;
+        };
+
+        let item = self.inventory.remove(i);
+        item.apply(self);
+        if let Some(old) = self.equipped[slot as usize].replace(item) {
+            old.unapply(self);
+            self.inventory.insert(i, old);
+        }
+
+        true
+    }
+
+    /// Moves whatever's in `slot` back into the inventory. Returns false
+    /// if there's no room for it.
+    pub fn unequip(&mut self, slot: Slot) -> bool {
+        let Some(item) = &self.equipped[slot as usize] else {
+            return true;
+        };
+        if !self.can_carry(item) {
+            return false;
+        }
+
+        let item = self.equipped[slot as usize].take().unwrap();
+        item.unapply(self);
+        self.inventory.push(item);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::item::Buff;
+
+    #[test]
+    fn equipping_and_unequipping_restores_stats() {
+        let mut player = Player {
+            damage: 1,
+            ..Default::default()
+        };
+        // like the troll hide: more than the player's speed of 0
+        player.inventory.push(Item {
+            name: "Hide".to_string(),
+            buffs: vec![Buff::Armor(2), Buff::Percent(Stat::Damage, 50)],
+            debuffs: vec![Buff::Speed(10)],
+            slot: Some(Slot::Armor),
+            ..Default::default()
+        });
+        let before = Stat::ALL.map(|stat| player.stat(stat));
+
+        for _ in 0..3 {
+            assert!(player.equip(0));
+            assert_eq!(player.stat(Stat::Speed), 0);
+            assert_eq!(player.stat(Stat::Armor), 2);
+
+            assert!(player.unequip(Slot::Armor));
+            assert_eq!(Stat::ALL.map(|stat| player.stat(stat)), before);
+        }
+    }
+
+    #[test]
+    fn statuses_wear_off_cleanly() {
+        let mut player = Player {
+            damage: 4,
+            max_health: 10,
+            health: 10,
+            ..Default::default()
+        };
+
+        player.add_status(StatusKind::Dehydrated, 3);
+        assert_eq!(player.damage(), 3);
+        player.end_status(0);
+        assert_eq!(player.damage(), 4);
+        assert!(player
+            .bonus
+            .values()
+            .chain(player.percent.values())
+            .all(|&b| b == 0));
+    }
+    #[test]
+    fn only_dodged_hits_miss() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut player = Player::default();
+        assert_eq!(player.incoming(0, &mut rng), Some(0));
+        assert_eq!(player.incoming(3, &mut rng), Some(3));
+
+        player.dodge = 100;
+        assert_eq!(player.incoming(3, &mut rng), None);
+    }
 }
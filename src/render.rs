@@ -0,0 +1,340 @@
+use std::cell::RefCell;
+use std::fmt::Display;
+
+use cod::BoxChars;
+use serde::Deserialize;
+use unicode_width::UnicodeWidthChar;
+
+use crate::palette;
+use crate::term;
+
+/// A terminal color. `Default` is whatever the terminal's own
+/// foreground or background is.
+///
+/// In theme files, an indexed color is a number and a 24-bit one is an
+/// `[r, g, b]` array.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(untagged)]
+pub enum Color {
+    #[default]
+    Default,
+    /// One of the 256 indexed colors.
+    Indexed(u8),
+    /// 24-bit true color.
+    Rgb(u8, u8, u8),
+}
+
+impl From<u8> for Color {
+    fn from(color: u8) -> Self {
+        Self::Indexed(color)
+    }
+}
+
+impl From<(u8, u8, u8)> for Color {
+    fn from((r, g, b): (u8, u8, u8)) -> Self {
+        Self::Rgb(r, g, b)
+    }
+}
+
+/// Fills the cell after a wide character (like most emoji), which the
+/// terminal draws the character's right half over.
+const CONTINUATION: char = '\0';
+
+/// The number of columns `ch` takes up on the terminal.
+pub fn char_width(ch: char) -> u32 {
+    ch.width().unwrap_or(0).max(1) as u32
+}
+
+/// The number of columns `text` takes up on the terminal.
+pub fn width(text: &str) -> u32 {
+    text.chars().map(char_width).sum()
+}
+
+/// One character on the screen and the colors it's drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: Color,
+    pub bg: Color,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            ch: ' ',
+            fg: Color::Default,
+            bg: Color::Default,
+        }
+    }
+}
+
+/// The frame being drawn, and the one last sent to the terminal. Rows
+/// grow to fit whatever is drawn into them; anything past the end of a
+/// row is blank.
+#[derive(Debug)]
+struct Screen {
+    /// The terminal's size as of the last `clear`. Anything drawn
+    /// outside it is cut off.
+    size: (u32, u32),
+    back: Vec<Vec<Cell>>,
+    front: Vec<Vec<Cell>>,
+    /// Set when the terminal was drawn on behind the buffer's back, so
+    /// `front` can't be trusted.
+    stale: bool,
+    fg: Color,
+    bg: Color,
+    cursor: (u32, u32),
+}
+
+impl Screen {
+    fn new() -> Self {
+        Self {
+            size: term::size(),
+            back: Vec::new(),
+            front: Vec::new(),
+            stale: true,
+            fg: Color::Default,
+            bg: Color::Default,
+            cursor: (0, 0),
+        }
+    }
+
+    /// Draws `ch` at (x, y). A wide character also takes the cell to
+    /// its right, or is left out if that's off the screen.
+    fn set(&mut self, ch: char, x: u32, y: u32) {
+        let wide = char_width(ch) == 2;
+        if x + wide as u32 >= self.size.0 || y >= self.size.1 {
+            return;
+        }
+
+        let cell = Cell {
+            ch,
+            fg: self.fg,
+            bg: self.bg,
+        };
+        self.put(cell, x as usize, y as usize);
+        if wide {
+            self.put(
+                Cell {
+                    ch: CONTINUATION,
+                    ..cell
+                },
+                x as usize + 1,
+                y as usize,
+            );
+        }
+    }
+
+    /// Puts a cell in the buffer, blanking out what's left of any wide
+    /// character it covers half of.
+    fn put(&mut self, cell: Cell, x: usize, y: usize) {
+        if self.back.len() <= y {
+            self.back.resize(y + 1, Vec::new());
+        }
+
+        let row = &mut self.back[y];
+        if row.len() <= x + 1 {
+            row.resize(x + 2, Cell::default());
+        }
+
+        if row[x].ch == CONTINUATION {
+            row[x - 1].ch = ' ';
+        }
+        if row[x + 1].ch == CONTINUATION && cell.ch != CONTINUATION {
+            row[x + 1].ch = ' ';
+        }
+
+        row[x] = cell;
+    }
+
+    fn print(&mut self, text: &str) {
+        for ch in text.chars() {
+            let (x, y) = self.cursor;
+            if ch == '\n' {
+                self.cursor = (0, y + 1);
+            } else {
+                self.set(ch, x, y);
+                self.cursor = (x + char_width(ch), y);
+            }
+        }
+    }
+
+    /// Sends every cell that differs from the last frame to the
+    /// terminal.
+    fn flush(&mut self) {
+        if self.stale {
+            cod::color::de();
+            cod::clear::all();
+            self.front.clear();
+            self.stale = false;
+        }
+
+        // what the terminal's cursor and colors are currently set to,
+        // so they're only sent when they change
+        let mut cursor = None;
+        let mut fg = None;
+        let mut bg = None;
+
+        let blank = Cell::default();
+        for y in 0..self.back.len().max(self.front.len()) {
+            let back = self.back.get(y).map_or(&[][..], |r| r);
+            let front = self.front.get(y).map_or(&[][..], |r| r);
+
+            for x in 0..back.len().max(front.len()) {
+                let new = back.get(x).unwrap_or(&blank);
+                // drawn along with the character before it
+                if new.ch == CONTINUATION || front.get(x).unwrap_or(&blank) == new {
+                    continue;
+                }
+
+                if cursor != Some((x, y)) {
+                    cod::goto::pos(x as u32, y as u32);
+                }
+                if fg != Some(new.fg) {
+                    palette::fg(new.fg);
+                    fg = Some(new.fg);
+                }
+                if bg != Some(new.bg) {
+                    palette::bg(new.bg);
+                    bg = Some(new.bg);
+                }
+
+                print!("{}", new.ch);
+                cursor = Some((x + char_width(new.ch) as usize, y));
+            }
+        }
+
+        self.front.clone_from(&self.back);
+
+        cod::color::de();
+        cod::goto::bot();
+        cod::flush();
+    }
+}
+
+thread_local! {
+    static SCREEN: RefCell<Screen> = RefCell::new(Screen::new());
+}
+
+fn with<T>(f: impl FnOnce(&mut Screen) -> T) -> T {
+    SCREEN.with_borrow_mut(f)
+}
+
+/// Starts a new frame: blanks the buffer and resets the colors and
+/// cursor. Nothing changes on the terminal until `flush`.
+///
+/// Also picks up the terminal's size. If it changed, the terminal will
+/// have reflowed whatever was on it, so the next flush redraws
+/// everything.
+pub fn clear() {
+    let size = term::size();
+    with(|s| {
+        if s.size != size {
+            s.size = size;
+            s.stale = true;
+        }
+
+        s.back.clear();
+        s.fg = Color::Default;
+        s.bg = Color::Default;
+        s.cursor = (0, 0);
+    });
+}
+
+/// The terminal's width and height as of the last `clear`.
+pub fn size() -> (u32, u32) {
+    with(|s| s.size)
+}
+
+/// Sends the changes since the last flush to the terminal.
+pub fn flush() {
+    with(Screen::flush);
+}
+
+/// Marks the terminal as drawn on by something other than the buffer
+/// (like a menu), so the next flush redraws everything.
+pub fn invalidate() {
+    with(|s| s.stale = true);
+}
+
+pub fn fg(color: impl Into<Color>) {
+    let color = color.into();
+    with(|s| s.fg = color);
+}
+
+pub fn bg(color: impl Into<Color>) {
+    let color = color.into();
+    with(|s| s.bg = color);
+}
+
+pub fn de_fg() {
+    fg(Color::Default);
+}
+
+pub fn de_bg() {
+    bg(Color::Default);
+}
+
+/// Resets both colors.
+pub fn de() {
+    de_fg();
+    de_bg();
+}
+
+/// Moves the cursor `print` writes at.
+pub fn goto(x: u32, y: u32) {
+    with(|s| s.cursor = (x, y));
+}
+
+/// Moves the cursor right without drawing over anything.
+pub fn right(n: u32) {
+    with(|s| s.cursor.0 += n);
+}
+
+/// Writes text at the cursor, moving it along. A newline moves it to
+/// the start of the next line.
+pub fn print(text: impl Display) {
+    let text = text.to_string();
+    with(|s| s.print(&text));
+}
+
+pub fn pixel(ch: char, x: u32, y: u32) {
+    with(|s| s.set(ch, x, y));
+}
+
+/// Writes text starting at (x, y), with each line below the last.
+pub fn blit(text: impl AsRef<str>, x: u32, y: u32) {
+    for (i, line) in text.as_ref().split('\n').enumerate() {
+        goto(x, y + i as u32);
+        print(line);
+    }
+}
+
+/// Draws a horizontal or vertical line between two points (inclusive).
+pub fn orth_line(ch: char, x1: u32, y1: u32, x2: u32, y2: u32) {
+    debug_assert!(x1 == x2 || y1 == y2, "line isn't orthogonal");
+    for y in y1.min(y2)..=y1.max(y2) {
+        for x in x1.min(x2)..=x1.max(x2) {
+            pixel(ch, x, y);
+        }
+    }
+}
+
+/// Fills the rectangle between two corners (inclusive) with blanks in
+/// the current background color.
+pub fn clear_rect(x1: u32, y1: u32, x2: u32, y2: u32) {
+    for y in y1..=y2 {
+        orth_line(' ', x1, y, x2, y);
+    }
+}
+
+/// Draws the outline of the rectangle between two corners (inclusive).
+pub fn rect_lines(chars: BoxChars, x1: u32, y1: u32, x2: u32, y2: u32) {
+    orth_line(chars.horizontal, x1, y1, x2, y1);
+    orth_line(chars.horizontal, x1, y2, x2, y2);
+    orth_line(chars.vertical, x1, y1, x1, y2);
+    orth_line(chars.vertical, x2, y1, x2, y2);
+    for (x, y) in [(x1, y1), (x1, y2), (x2, y1), (x2, y2)] {
+        pixel(chars.corner, x, y);
+    }
+}
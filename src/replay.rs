@@ -0,0 +1,70 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use zstd::stream::Decoder;
+
+use crate::action::Action;
+use crate::save;
+
+/// Everything needed to reproduce a run: where it started, and every
+/// action fed to `World::update` along with the turn it was taken on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    /// The world manifest the run was played on, or `None` for the
+    /// built-in world.
+    pub world: Option<PathBuf>,
+    /// Whether diagonal movement was on.
+    #[serde(default)]
+    pub diagonal: bool,
+    pub actions: Vec<(u32, Action)>,
+    /// Set once the run stops being reproducible (e.g. a save was loaded).
+    #[serde(skip)]
+    stopped: bool,
+}
+
+impl Replay {
+    pub fn new(seed: u64, world: Option<PathBuf>, diagonal: bool) -> Self {
+        Self {
+            seed,
+            world,
+            diagonal,
+            actions: Vec::new(),
+            stopped: false,
+        }
+    }
+
+    pub fn record(&mut self, turn: u32, action: &Action) {
+        if !self.stopped {
+            self.actions.push((turn, action.clone()));
+        }
+    }
+
+    /// Stops recording; the replay will only cover the run up to here.
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+
+    pub fn save_to<P: AsRef<Path>>(&self, file: P) -> io::Result<()> {
+        let json = serde_json::to_string(self)?;
+
+        let mut file = BufWriter::new(File::create(file)?);
+        zstd::stream::copy_encode(json.as_bytes(), &mut file, 0)?;
+        file.flush()
+    }
+
+    pub fn load_from<P: AsRef<Path>>(file: P) -> io::Result<Self> {
+        let file = BufReader::new(File::open(file)?);
+        let stream = Decoder::new(file)?;
+        Ok(serde_json::from_reader(stream)?)
+    }
+}
+
+/// Where the most recent run is recorded unless `--record` says otherwise.
+pub fn default_path() -> PathBuf {
+    let mut dir = save::data_dir();
+    dir.push("frob-replay-last.json.zst");
+    dir
+}
@@ -1,15 +1,22 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
 use std::fs::{self, File, OpenOptions};
-use std::io::{BufReader, BufWriter, Write};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use zstd::stream::Decoder;
 
+use crate::difficulty::Difficulty;
+use crate::entity::Entity;
+use crate::item::Item;
 use crate::map::Tile;
-use crate::player::Player;
-use crate::sector::HEIGHT;
-use crate::world::World;
+use crate::player::{Player, Stat};
+use crate::term;
+use crate::world::{GameMode, World};
 
 #[derive(Serialize, Deserialize)]
 struct SaveData {
@@ -17,6 +24,23 @@ struct SaveData {
     current_sector: String,
     tile_changes: HashMap<String, Vec<(u32, u32, Tile)>>,
     despawned: Vec<(String, u32)>,
+    /// The entities in every sector. Missing in saves from before v3, in
+    /// which case sectors are reset and only `despawned` is applied.
+    #[serde(default)]
+    entities: Option<HashMap<String, Vec<Entity>>>,
+    turn: u32,
+    difficulty: Difficulty,
+    /// The seed and the low half of the rng's word position.
+    #[serde(default)]
+    rng: Option<(u64, u64)>,
+    /// The high half of the word position, which doesn't fit in `rng`.
+    #[serde(default)]
+    rng_high: u64,
+    #[serde(default)]
+    diagonal: bool,
+    /// Stored in the header rather than the data.
+    #[serde(skip)]
+    mode: GameMode,
 }
 
 impl SaveData {
@@ -33,7 +57,7 @@ impl SaveData {
                 tiles.push((x, y, tile));
             }
 
-            tile_changes.insert(id.to_string(), std::mem::take(&mut tiles));
+            tile_changes.insert(id.clone(), std::mem::take(&mut tiles));
         }
 
         tiles.clear();
@@ -45,25 +69,61 @@ impl SaveData {
             tiles.push((x, y, tile));
         }
 
-        tile_changes.insert(world.map.sector().id.to_string(), tiles);
+        tile_changes.insert(world.map.sector().id.clone(), tiles);
 
         let despawned = world
             .despawned
             .iter()
-            .map(|(s, i)| (s.to_string(), *i))
+            .map(|(s, i)| (s.clone(), *i))
             .collect();
 
+        let mut entities: HashMap<_, _> = world
+            .map
+            .sectors
+            .iter()
+            .map(|(id, sector)| (id.clone(), sector.entities().to_vec()))
+            .collect();
+        entities.insert(world.map.sector().id.clone(), world.entities.clone());
+
+        let pos = world.rng.get_word_pos();
         Self {
             player: world.player.clone(),
-            current_sector: world.map.current_sector.id.to_string(),
+            current_sector: world.map.current_sector.id.clone(),
             tile_changes,
             despawned,
+            entities: Some(entities),
+            turn: world.turn,
+            difficulty: world.difficulty,
+            rng: Some((world.seed, pos as u64)),
+            rng_high: (pos >> 64) as u64,
+            diagonal: world.diagonal,
+            mode: world.mode,
         }
     }
 
-    pub fn apply(self, world: &mut World) {
+    pub fn apply(self, world: &mut World) -> Result<(), SaveError> {
+        if world.map.get_sector(&self.current_sector).is_none() {
+            return Err(SaveError::Corrupt(format!(
+                "unknown sector `{}`",
+                self.current_sector
+            )));
+        }
+
+        world.reset_map();
         world.map.load(&self.current_sector);
         world.player = self.player;
+        world.turn = self.turn;
+        world.difficulty = self.difficulty;
+        world.mode = self.mode;
+        world.diagonal = self.diagonal;
+
+        if let Some((seed, pos)) = self.rng {
+            world.seed = seed;
+            world.rng = ChaCha8Rng::seed_from_u64(seed);
+            world
+                .rng
+                .set_word_pos(((self.rng_high as u128) << 64) | pos as u128);
+        }
 
         for (sector, changes) in self.tile_changes {
             if let Some(sector) = world.map.get_sector_mut(&sector) {
@@ -73,84 +133,520 @@ impl SaveData {
             }
         }
 
+        world.despawned.clear();
         for (sector, id) in self.despawned {
             if let Some(sector) = world.map.get_sector_mut(&sector) {
-                sector.despawn_id(id);
-                world.despawned.push((sector.id, id));
+                if self.entities.is_none() {
+                    sector.despawn_id(id);
+                }
+                world.despawned.push((sector.id.clone(), id));
+            }
+        }
+
+        for (sector, entities) in self.entities.into_iter().flatten() {
+            if let Some(sector) = world.map.get_sector_mut(&sector) {
+                sector.save_entities(entities);
             }
         }
 
         world.entities = world.map.sector().entities().to_vec();
+        Ok(())
+    }
+}
+
+/// Save files start with these bytes, followed by the format version
+/// as a little-endian `u16`, the game mode as a single byte (since v4)
+/// and then the zstd-compressed JSON data.
+///
+/// Files from before the header was introduced are bare zstd streams;
+/// those are treated as version 1.
+const MAGIC: &[u8; 4] = b"FROB";
+const VERSION: u16 = 5;
+
+/// Upgrades save data from one version to the next.
+type Migration = fn(Value) -> Result<Value, SaveError>;
+
+/// `MIGRATIONS[i]` turns version `i + 1` into version `i + 2`.
+const MIGRATIONS: &[Migration] = &[
+    // v1 -> v2: only the header was added
+    Ok, migrate_v2, // v3 -> v4: only the game mode was added to the header
+    Ok, migrate_v4,
+];
+
+/// v2 -> v3: the turn counter and difficulty are now saved.
+fn migrate_v2(mut value: Value) -> Result<Value, SaveError> {
+    let Some(data) = value.as_object_mut() else {
+        return Err(SaveError::Corrupt("expected an object".to_string()));
+    };
+
+    data.insert("turn".to_string(), Value::from(0));
+    data.insert(
+        "difficulty".to_string(),
+        serde_json::to_value(Difficulty::normal())?,
+    );
+
+    Ok(value)
+}
+
+/// v4 -> v5: the buffs of carried items used to be added onto the
+/// player's base stats. They're now kept in `bonus`.
+fn migrate_v4(mut value: Value) -> Result<Value, SaveError> {
+    let Some(player) = value.get_mut("player").and_then(Value::as_object_mut) else {
+        return Err(SaveError::Corrupt("expected a player".to_string()));
+    };
+
+    let inventory: Vec<Item> =
+        serde_json::from_value(player.get("inventory").cloned().unwrap_or_default())?;
+    let mut bonus = BTreeMap::<Stat, i32>::new();
+    for item in &inventory {
+        let buffs = item.buffs.iter().map(|buff| (buff, 1));
+        let debuffs = item.debuffs.iter().map(|buff| (buff, -1));
+        for (buff, sign) in buffs.chain(debuffs) {
+            *bonus.entry(buff.stat()).or_default() += sign * (buff.diff() * item.count) as i32;
+        }
     }
+
+    for (stat, diff) in &bonus {
+        let field = match stat {
+            Stat::MaxHealth => "max_health",
+            Stat::Damage => "damage",
+            Stat::ThirstCap => "thirst_cap",
+            Stat::HungerCap => "hunger_cap",
+            Stat::Armor => "armor",
+            Stat::Dodge => "dodge",
+            Stat::Vision => "vision",
+            Stat::Speed => "speed",
+        };
+        if let Some(base) = player.get(field).and_then(Value::as_i64) {
+            player.insert(field.to_string(), Value::from((base - *diff as i64).max(0)));
+        }
+    }
+    player.insert("bonus".to_string(), serde_json::to_value(bonus)?);
+
+    Ok(value)
 }
 
-pub fn save_to<S: AsRef<Path>>(file: S, world: &World) -> bool {
-    let data = SaveData::from(world);
+#[derive(Debug)]
+pub enum SaveError {
+    NotFound,
+    Io(io::Error),
+    Corrupt(String),
+    TooNew(u16),
+}
 
-    let json = serde_json::to_string(&data).expect("Failed to serialize save data");
+impl Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "Save not found"),
+            Self::Io(e) => write!(f, "Couldn't access save: {e}"),
+            Self::Corrupt(e) => write!(f, "Save is corrupt: {e}"),
+            Self::TooNew(v) => write!(f, "Save is from a newer version (v{v})"),
+        }
+    }
+}
 
-    let Ok(file) = OpenOptions::new()
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::NotFound {
+            Self::NotFound
+        } else {
+            Self::Io(e)
+        }
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Corrupt(e.to_string())
+    }
+}
+
+/// Writes the save next to `path` first and only moves it into place
+/// once it's complete, so a crash mid-write can't clobber the old save.
+fn write_save(path: &Path, data: &SaveData, backups: u32) -> Result<(), SaveError> {
+    let json = serde_json::to_string(data)?;
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let file = OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
-        .open(file)
-    else {
-        world.draw_message("Save not found", 1);
-        cod::read::key();
-        return false;
-    };
+        .open(&tmp)?;
     let mut file = BufWriter::new(file);
-    zstd::stream::copy_encode(json.as_bytes(), &mut file, 0).expect("Failed to write to save file");
 
-    file.flush().expect("Failed to write to save file");
-    true
+    file.write_all(MAGIC)?;
+    file.write_all(&VERSION.to_le_bytes())?;
+    file.write_all(&[mode_byte(data.mode)])?;
+    zstd::stream::copy_encode(json.as_bytes(), &mut file, 0)?;
+
+    file.flush()?;
+    drop(file);
+
+    rotate(path, backups)?;
+    fs::rename(tmp, path)?;
+    Ok(())
 }
 
-pub fn save(world: &World) -> bool {
-    if let Some(slot) = get_slot() {
-        let mut dir = dirs::data_dir().unwrap_or_else(|| PathBuf::from("./"));
-        dir.push("frob-adventure");
-        fs::create_dir_all(&dir).expect("Failed to create save directory");
-        dir.push(format!("frob-save-{slot}.json.zst"));
-        save_to(dir, world)
+/// The path of the `n`th most recent backup of a save file.
+pub fn backup_path(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// Moves `path` into its first backup slot, shifting the existing
+/// backups along by one and dropping any past `backups`.
+fn rotate(path: &Path, backups: u32) -> io::Result<()> {
+    if backups == 0 || !path.exists() {
+        return Ok(());
+    }
+
+    for n in (1..backups).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            fs::rename(from, backup_path(path, n + 1))?;
+        }
+    }
+
+    fs::rename(path, backup_path(path, 1))
+}
+
+fn read_save<S: AsRef<Path>>(file: S) -> Result<SaveData, SaveError> {
+    let mut file = BufReader::new(File::open(file)?);
+
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)
+        .map_err(|_| SaveError::Corrupt("file is too short".to_string()))?;
+
+    let version = if &magic == MAGIC {
+        let mut version = [0; 2];
+        file.read_exact(&mut version)
+            .map_err(|_| SaveError::Corrupt("missing version".to_string()))?;
+        u16::from_le_bytes(version)
+    } else {
+        file.seek(SeekFrom::Start(0))?;
+        1
+    };
+
+    if version > VERSION {
+        return Err(SaveError::TooNew(version));
+    } else if version == 0 {
+        return Err(SaveError::Corrupt("invalid version 0".to_string()));
+    }
+
+    let mode = if version >= 4 {
+        let mut mode = [0];
+        file.read_exact(&mut mode)
+            .map_err(|_| SaveError::Corrupt("missing game mode".to_string()))?;
+        match mode[0] {
+            0 => GameMode::Normal,
+            1 => GameMode::Ironman,
+            m => return Err(SaveError::Corrupt(format!("unknown game mode {m}"))),
+        }
     } else {
-        false
+        GameMode::Normal
+    };
+
+    let stream = Decoder::new(file)?;
+    let mut value: Value = serde_json::from_reader(stream)?;
+    for migrate in &MIGRATIONS[version as usize - 1..] {
+        value = migrate(value)?;
     }
+
+    let mut data: SaveData = serde_json::from_value(value)?;
+    data.mode = mode;
+    Ok(data)
 }
 
-fn get_slot() -> Option<String> {
-    cod::goto::pos(0, HEIGHT as u32);
-    cod::clear::line();
-    cod::color::de_bg();
-    cod::color::fg(2);
-    print!("Save slot: ");
-    cod::color::de_fg();
-    cod::flush();
-    cod::read::line()
+fn mode_byte(mode: GameMode) -> u8 {
+    match mode {
+        GameMode::Normal => 0,
+        GameMode::Ironman => 1,
+    }
 }
 
+/// Deletes a save file along with its backups.
+pub fn delete(path: &Path) -> io::Result<()> {
+    fs::remove_file(path)?;
+
+    let mut n = 1;
+    while backup_path(path, n).exists() {
+        fs::remove_file(backup_path(path, n))?;
+        n += 1;
+    }
+
+    Ok(())
+}
+
+/// Saves the world to `file`, keeping up to `backups` older copies.
+pub fn save_to<S: AsRef<Path>>(file: S, world: &World, backups: u32) -> bool {
+    match write_save(file.as_ref(), &SaveData::from(world), backups) {
+        Ok(()) => true,
+        Err(e) => {
+            world.draw_message(e, 1);
+            term::key();
+            false
+        }
+    }
+}
+
+/// The directory saves (and other per-user data) are kept in.
+pub fn data_dir() -> PathBuf {
+    let mut dir = dirs::data_dir().unwrap_or_else(|| PathBuf::from("./"));
+    dir.push("frob-adventure");
+    fs::create_dir_all(&dir).expect("Failed to create save directory");
+    dir
+}
+
+/// The file a named save slot is stored in.
+pub fn slot_path(slot: &str) -> PathBuf {
+    let mut dir = data_dir();
+    dir.push(format!("frob-save-{slot}.json.zst"));
+    dir
+}
+
+/// A summary of a save, for showing without loading it.
+#[derive(Debug, Clone)]
+pub struct SaveInfo {
+    pub sector: String,
+    pub position: (u32, u32),
+    pub turn: u32,
+    pub health: u32,
+    pub max_health: u32,
+    pub items: usize,
+    pub difficulty: Difficulty,
+    pub mode: GameMode,
+}
+
+pub fn info<S: AsRef<Path>>(file: S) -> Result<SaveInfo, SaveError> {
+    let data = read_save(file)?;
+    Ok(SaveInfo {
+        sector: data.current_sector,
+        position: (data.player.x, data.player.y),
+        turn: data.turn,
+        health: data.player.health,
+        max_health: data.player.max_health(),
+        items: data.player.inventory.len(),
+        difficulty: data.difficulty,
+        mode: data.mode,
+    })
+}
+
+/// Loads the save in `file` into the world. Ironman saves are deleted
+/// once loaded, so they can't be loaded a second time.
 pub fn load_from<S: AsRef<Path>>(file: S, world: &mut World) -> bool {
-    let Ok(file) = File::open(file) else {
-        world.draw_message("Save not found", 1);
-        cod::read::key();
-        return false;
-    };
-    let file = BufReader::new(file);
-    let stream = Decoder::new(file).expect("Failed to create decoder");
-    let data: SaveData = serde_json::from_reader(stream).expect("Failed to parse save file");
-    data.apply(world);
-    true
-}
-
-pub fn load(world: &mut World) -> bool {
-    if let Some(slot) = get_slot() {
-        let mut dir = dirs::data_dir().unwrap_or_else(|| PathBuf::from("./"));
-        dir.push("frob-adventure");
-        fs::create_dir_all(&dir).expect("Failed to create save directory");
-        dir.push(format!("frob-save-{slot}.json.zst"));
-        load_from(dir, world)
-    } else {
-        cod::read::key();
-        false
+    let file = file.as_ref();
+    let res = read_save(file)
+        .and_then(|data| data.apply(world))
+        .and_then(|()| {
+            if world.mode == GameMode::Ironman {
+                delete(file)?;
+            }
+            Ok(())
+        });
+    match res {
+        Ok(()) => true,
+        Err(e) => {
+            world.draw_message(e, 1);
+            term::key();
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Action;
+    use crate::item::Buff;
+    use crate::map::Direction;
+    use crate::world_map;
+
+    /// A fresh directory for a test to write saves into.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("frob-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes `value` the way saves of `version` were written.
+    fn write_version(path: &Path, version: u16, value: &Value) {
+        let mut bytes = Vec::new();
+        // v1 saves had no header
+        if version != 1 {
+            bytes.extend(MAGIC);
+            bytes.extend(version.to_le_bytes());
+        }
+        if version >= 4 {
+            bytes.push(mode_byte(GameMode::Normal));
+        }
+        let json = serde_json::to_vec(value).unwrap();
+        bytes.extend(zstd::encode_all(&json[..], 0).unwrap());
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn old_saves_are_migrated() {
+        let dir = temp_dir("migrations");
+        let world = World::new(world_map::builtin().unwrap(), 2);
+
+        // before v3 there was no turn counter or difficulty
+        let mut value = serde_json::to_value(SaveData::from(&world)).unwrap();
+        let data = value.as_object_mut().unwrap();
+        data.remove("turn");
+        data.remove("difficulty");
+
+        for version in [1, 2] {
+            let path = dir.join(format!("v{version}.json.zst"));
+            write_version(&path, version, &value);
+
+            let data = read_save(&path).unwrap();
+            assert_eq!(data.turn, 0);
+            assert_eq!(data.difficulty, Difficulty::normal());
+            assert_eq!(data.player, world.player);
+            assert_eq!(data.mode, GameMode::Normal);
+        }
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn bad_saves_are_rejected() {
+        let dir = temp_dir("bad");
+        let path = dir.join("bad.json.zst");
+        let world = World::new(world_map::builtin().unwrap(), 2);
+        let value = serde_json::to_value(SaveData::from(&world)).unwrap();
+
+        assert!(matches!(read_save(&path), Err(SaveError::NotFound)));
+
+        write_version(&path, VERSION + 1, &value);
+        assert!(matches!(read_save(&path), Err(SaveError::TooNew(v)) if v == VERSION + 1));
+
+        write_version(&path, 0, &value);
+        assert!(matches!(read_save(&path), Err(SaveError::Corrupt(_))));
+
+        fs::write(&path, b"FR").unwrap();
+        assert!(matches!(read_save(&path), Err(SaveError::Corrupt(_))));
+
+        write_version(&path, VERSION, &Value::from("not a save"));
+        assert!(matches!(read_save(&path), Err(SaveError::Corrupt(_))));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn backups_rotate() {
+        let dir = temp_dir("backups");
+        let path = dir.join("slot.json.zst");
+        let mut world = World::new(world_map::builtin().unwrap(), 3);
+
+        for turn in 1..=4 {
+            world.turn = turn;
+            assert!(save_to(&path, &world, 2));
+        }
+
+        assert_eq!(read_save(&path).unwrap().turn, 4);
+        assert_eq!(read_save(backup_path(&path, 1)).unwrap().turn, 3);
+        assert_eq!(read_save(backup_path(&path, 2)).unwrap().turn, 2);
+        assert!(!backup_path(&path, 3).exists());
+
+        delete(&path).unwrap();
+        assert!(!path.exists() && !backup_path(&path, 1).exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn ironman_saves_can_only_be_loaded_once() {
+        let dir = temp_dir("ironman");
+        let path = dir.join("ironman.json.zst");
+        let def = world_map::builtin().unwrap();
+        let mut world = World::new(def.clone(), 5);
+        world.mode = GameMode::Ironman;
+        world.update(Action::Move(Direction::Up)).unwrap();
+        assert!(save_to(&path, &world, 0));
+
+        let mut loaded = World::new(def, 0);
+        assert!(load_from(&path, &mut loaded));
+        assert_eq!(loaded.mode, GameMode::Ironman);
+        assert_eq!(loaded.turn, world.turn);
+        assert!(!path.exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let def = world_map::builtin().unwrap();
+        let mut world = World::new(def.clone(), 7);
+        for direction in [
+            Direction::Up,
+            Direction::Left,
+            Direction::Left,
+            Direction::Up,
+        ] {
+            world.update(Action::Move(direction)).unwrap();
+        }
+
+        let json = serde_json::to_string(&SaveData::from(&world)).unwrap();
+        let data: SaveData = serde_json::from_str(&json).unwrap();
+        let mut loaded = World::new(def, 0);
+        data.apply(&mut loaded).unwrap();
+
+        assert_eq!(loaded.player, world.player);
+        assert_eq!(loaded.turn, world.turn);
+        assert_eq!(loaded.entities, world.entities);
+        assert_eq!(loaded.map.sector().id, world.map.sector().id);
+
+        // the rng picks up where it left off, so both play out the same
+        for direction in [Direction::Right, Direction::Up] {
+            assert_eq!(
+                loaded.update(Action::Move(direction)),
+                world.update(Action::Move(direction))
+            );
+        }
+        assert_eq!(loaded.entities, world.entities);
+    }
+    #[test]
+    fn v4_item_buffs_move_out_of_base_stats() {
+        let mut world = World::new(world_map::builtin().unwrap(), 1);
+        let base = world.player.clone();
+        world.player.add_item(Item::full(
+            "Amulet",
+            0,
+            vec![Buff::MaxHealth(5)],
+            vec![Buff::Damage(1)],
+        ));
+
+        // how v4 stored it: the buffs added straight onto the stats
+        let mut value = serde_json::to_value(SaveData::from(&world)).unwrap();
+        let player = value["player"].as_object_mut().unwrap();
+        player.remove("bonus");
+        player.insert("max_health".to_string(), Value::from(base.max_health + 5));
+        player.insert("damage".to_string(), Value::from(base.damage - 1));
+
+        let data: SaveData = serde_json::from_value(migrate_v4(value).unwrap()).unwrap();
+        assert_eq!(data.player, world.player);
+
+        let mut player = data.player;
+        player.remove_item(0);
+        assert_eq!(player.max_health(), base.max_health());
+        assert_eq!(player.damage(), base.damage());
+    }
+    #[test]
+    fn rng_position_is_saved_in_full() {
+        let def = world_map::builtin().unwrap();
+        let mut world = World::new(def.clone(), 3);
+        let pos = (5 << 64) | 12;
+        world.rng.set_word_pos(pos);
+
+        let json = serde_json::to_string(&SaveData::from(&world)).unwrap();
+        let data: SaveData = serde_json::from_str(&json).unwrap();
+        let mut loaded = World::new(def, 0);
+        data.apply(&mut loaded).unwrap();
+
+        assert_eq!(loaded.seed, 3);
+        assert_eq!(loaded.rng.get_word_pos(), pos);
     }
 }
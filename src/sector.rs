@@ -7,69 +7,29 @@ pub const HEIGHT: usize = 16;
 
 #[derive(Debug, Clone, PartialEq, Hash)]
 pub struct Sector {
-    pub id: &'static str,
+    pub id: String,
     tiles: [[Tile; WIDTH]; HEIGHT],
     entities: Vec<Entity>,
-    neighbors: [Option<&'static str>; 4],
+    neighbors: [Option<String>; 8],
     changed: Vec<(u32, u32)>,
     pub difficulty: Difficulty,
     pub do_survival: bool,
-    entrances: Vec<(u32, u32, &'static str)>,
+    entrances: Vec<(u32, u32, String)>,
     pub return_tile: Option<(u32, u32)>,
 }
 
 impl Sector {
     pub fn new(
         map: &str,
-        id: &'static str,
+        id: impl Into<String>,
         entities: Vec<Entity>,
-        neighbors: [Option<&'static str>; 4],
+        neighbors: [Option<String>; 8],
     ) -> Self {
         let mut tiles = [[Tile::default(); WIDTH]; HEIGHT];
         let mut x = 0;
         let mut y = 0;
         for ch in map.trim().chars() {
             match ch {
-                '~' => {
-                    tiles[y][x] = Tile {
-                        kind: TileKind::Water,
-                    }
-                }
-                '_' => {
-                    tiles[y][x] = Tile {
-                        kind: TileKind::Grass,
-                    }
-                }
-                '$' => {
-                    tiles[y][x] = Tile {
-                        kind: TileKind::Forest,
-                    }
-                }
-                'n' => {
-                    tiles[y][x] = Tile {
-                        kind: TileKind::Hill,
-                    }
-                }
-                'A' => {
-                    tiles[y][x] = Tile {
-                        kind: TileKind::Mountain,
-                    }
-                }
-                '=' => {
-                    tiles[y][x] = Tile {
-                        kind: TileKind::Road,
-                    }
-                }
-                '%' => {
-                    tiles[y][x] = Tile {
-                        kind: TileKind::Village,
-                    }
-                }
-                '^' => {
-                    tiles[y][x] = Tile {
-                        kind: TileKind::Building,
-                    }
-                }
                 '\n' => {
                     if x != 0 {
                         y += 1;
@@ -81,8 +41,12 @@ impl Sector {
 
                     continue;
                 }
-                ' ' | '\t' => continue,
-                _ => panic!("invalid tile: `{ch}`"),
+                ' ' | '\t' | '\r' => continue,
+                _ => {
+                    let kind =
+                        TileKind::from_char(ch).unwrap_or_else(|| panic!("invalid tile: `{ch}`"));
+                    tiles[y][x] = Tile { kind };
+                }
             }
 
             x += 1;
@@ -96,7 +60,7 @@ impl Sector {
         }
 
         Sector {
-            id,
+            id: id.into(),
             tiles,
             entities,
             neighbors,
@@ -108,29 +72,15 @@ impl Sector {
         }
     }
 
-    pub fn entrance(&mut self, x: u32, y: u32, id: &'static str) {
-        self.entrances.push((x, y, id));
-    }
-
-    pub fn get_entrance(&self, x: u32, y: u32) -> Option<&'static str> {
-        self.entrances.iter().find(|(tx, ty, _)| (*tx, *ty) == (x, y)).map(|(_, _, id)| *id)
+    pub fn entrance(&mut self, x: u32, y: u32, id: impl Into<String>) {
+        self.entrances.push((x, y, id.into()));
     }
 
-    pub fn town(
-        map: &str,
-        id: &'static str,
-        entities: Vec<Entity>,
-        outside: &'static str,
-        return_x: u32,
-        return_y: u32,
-    ) -> Self {
-        let mut s = Self::new(map, id, entities, [Some(outside); 4]);
-        
-        s.do_survival = false;
-        s.difficulty = Difficulty::none();
-        s.return_tile = Some((return_x, return_y));
-
-        s
+    pub fn get_entrance(&self, x: u32, y: u32) -> Option<&str> {
+        self.entrances
+            .iter()
+            .find(|(tx, ty, _)| (*tx, *ty) == (x, y))
+            .map(|(_, _, id)| id.as_str())
     }
 
     pub fn entities(&self) -> &[Entity] {
@@ -166,12 +116,12 @@ impl Sector {
     }
 
     #[allow(dead_code)]
-    pub fn add_neighbor(&mut self, direction: Direction, neighbor: &'static str) {
-        self.neighbors[direction as usize] = Some(neighbor);
+    pub fn add_neighbor(&mut self, direction: Direction, neighbor: impl Into<String>) {
+        self.neighbors[direction as usize] = Some(neighbor.into());
     }
 
-    pub fn neighbor(&self, direction: Direction) -> Option<&'static str> {
-        self.neighbors[direction as usize]
+    pub fn neighbor(&self, direction: Direction) -> Option<&str> {
+        self.neighbors[direction as usize].as_deref()
     }
 
     pub fn tiles(&self) -> &[[Tile; WIDTH]; HEIGHT] {
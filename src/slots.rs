@@ -0,0 +1,398 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use cod::Key;
+
+use crate::map::HEIGHT;
+use crate::palette;
+use crate::render;
+use crate::save::{self, SaveError, SaveInfo};
+use crate::term;
+use crate::theme;
+use crate::world::{GameMode, World};
+
+const PREFIX: &str = "frob-save-";
+const SUFFIX: &str = ".json.zst";
+
+const PREVIEW_X: u32 = 46;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Save,
+    Load,
+}
+
+#[derive(Debug)]
+pub struct Slot {
+    pub name: String,
+    /// Which backup of the slot this is, if it isn't the slot itself.
+    pub backup: Option<u32>,
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    pub info: Result<SaveInfo, SaveError>,
+}
+
+/// Lists every save slot in the data directory, most recent first.
+/// If `backups` is set, each slot's backups are listed after it.
+pub fn list(backups: bool) -> Vec<Slot> {
+    let Ok(dir) = fs::read_dir(save::data_dir()) else {
+        return Vec::new();
+    };
+
+    let slots: Vec<_> = dir
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let name = file_name.to_str()?.strip_prefix(PREFIX)?;
+            let (name, backup) = match name.strip_suffix(SUFFIX) {
+                Some(name) => (name, None),
+                None => {
+                    let (name, n) = name.rsplit_once(SUFFIX)?;
+                    (name, Some(n.strip_prefix('.')?.parse().ok()?))
+                }
+            };
+
+            if backup.is_some() && !backups {
+                return None;
+            }
+
+            let path = entry.path();
+            Some(Slot {
+                name: name.to_string(),
+                backup,
+                modified: entry.metadata().and_then(|m| m.modified()).ok(),
+                info: save::info(&path),
+                path,
+            })
+        })
+        .collect();
+
+    let (mut sorted, mut backups): (Vec<_>, Vec<_>) =
+        slots.into_iter().partition(|s| s.backup.is_none());
+    sorted.sort_by_key(|s| Reverse(s.modified));
+    backups.sort_by_key(|s| s.backup);
+
+    // keep backups right after the slot they belong to
+    for backup in backups {
+        let i = sorted
+            .iter()
+            .rposition(|s| s.name == backup.name)
+            .map_or(sorted.len(), |i| i + 1);
+        sorted.insert(i, backup);
+    }
+
+    sorted
+}
+
+/// Quicksaves (or autosaves) to the given slot, skipping the browser.
+pub fn save_quick(world: &World, slot: &str, backups: u32) -> bool {
+    save::save_to(save::slot_path(slot), world, backups)
+}
+
+/// Loads the given slot, skipping the browser.
+pub fn load_quick(world: &mut World, slot: &str) -> bool {
+    save::load_from(save::slot_path(slot), world)
+}
+
+/// Opens the slot browser to pick a slot to save to.
+pub fn save(world: &mut World, backups: u32) -> bool {
+    browse(world, Mode::Save, backups)
+}
+
+/// Opens the slot browser to pick a save (or backup) to load.
+pub fn load(world: &mut World) -> bool {
+    browse(world, Mode::Load, 0)
+}
+
+fn browse(world: &mut World, mode: Mode, backups: u32) -> bool {
+    let mut selected = 0;
+
+    loop {
+        let slots = list(mode == Mode::Load);
+        // in save mode, the first entry creates a new slot
+        let offset = (mode == Mode::Save) as usize;
+        let entries = slots.len() + offset;
+        selected = selected.min(entries.saturating_sub(1));
+
+        let bottom = draw(world, &slots, selected, mode);
+        let slot = selected.checked_sub(offset).and_then(|i| slots.get(i));
+
+        let Some(key) = term::key() else {
+            continue;
+        };
+
+        match key {
+            Key::ArrowUp => selected = selected.saturating_sub(1),
+            Key::ArrowDown => selected = (selected + 1).min(entries.saturating_sub(1)),
+
+            Key::Enter => match (mode, slot) {
+                (Mode::Save, None) => {
+                    let Some(name) = prompt("New slot name: ", bottom) else {
+                        continue;
+                    };
+
+                    if slots.iter().any(|s| s.name == name)
+                        && !confirm(&format!("Overwrite `{name}` (y/N)?"), bottom)
+                    {
+                        continue;
+                    }
+
+                    return save::save_to(save::slot_path(&name), world, backups);
+                }
+                (Mode::Save, Some(slot)) => {
+                    if confirm(&format!("Overwrite `{}` (y/N)?", slot.name), bottom) {
+                        return save::save_to(&slot.path, world, backups);
+                    }
+                }
+                (Mode::Load, Some(slot)) => return save::load_from(&slot.path, world),
+                (Mode::Load, None) => {}
+            },
+
+            Key::Char('r' | 'R') => {
+                let Some(slot) = slot else { continue };
+                let Some(name) = prompt(&format!("Rename `{}` to: ", slot.name), bottom) else {
+                    continue;
+                };
+
+                // renaming a backup restores it as a slot of its own
+                if slots.iter().any(|s| s.name == name && s.backup.is_none()) {
+                    message(&format!("`{name}` already exists"), bottom);
+                } else if let Err(e) = rename(slot, &name) {
+                    message(&format!("Couldn't rename: {e}"), bottom);
+                }
+            }
+
+            Key::Char('x' | 'X') | Key::Del => {
+                let Some(slot) = slot else { continue };
+                if confirm(&format!("Delete `{}` (y/N)?", label(slot)), bottom) {
+                    if let Err(e) = delete(slot) {
+                        message(&format!("Couldn't delete: {e}"), bottom);
+                    }
+                }
+            }
+
+            Key::Char('q' | 'Q') | Key::Escape => return false,
+            _ => {}
+        }
+    }
+}
+
+fn label(slot: &Slot) -> String {
+    match slot.backup {
+        Some(n) => format!("{} (backup {n})", slot.name),
+        None => slot.name.clone(),
+    }
+}
+
+/// Renames a slot along with its backups.
+fn rename(slot: &Slot, name: &str) -> io::Result<()> {
+    let to = save::slot_path(name);
+    fs::rename(&slot.path, &to)?;
+
+    if slot.backup.is_none() {
+        let mut n = 1;
+        while save::backup_path(&slot.path, n).exists() {
+            fs::rename(save::backup_path(&slot.path, n), save::backup_path(&to, n))?;
+            n += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes a slot along with its backups.
+fn delete(slot: &Slot) -> io::Result<()> {
+    if slot.backup.is_none() {
+        save::delete(&slot.path)
+    } else {
+        fs::remove_file(&slot.path)
+    }
+}
+
+/// Draws the browser. Returns the first free line below it.
+fn draw(world: &World, slots: &[Slot], selected: usize, mode: Mode) -> u32 {
+    cod::clear::all();
+    render::invalidate();
+    cod::color::de();
+
+    let title = match mode {
+        Mode::Save => "- Save game -+",
+        Mode::Load => "- Load game -+",
+    };
+    cod::pixel('+', 0, 0);
+    cod::blit(title, 1, 0);
+
+    let mut y = 1;
+    let mut entry = 0;
+    let mut preview = None;
+
+    if mode == Mode::Save {
+        if selected == entry {
+            cod::pixel('-', 1, y);
+        }
+        palette::fg(2);
+        cod::blit("[New slot]", 3, y);
+        cod::color::de_fg();
+        y += 2;
+        entry += 1;
+    } else if slots.is_empty() {
+        palette::fg(1);
+        cod::blit("No saves found", 3, y);
+        cod::color::de_fg();
+        y += 2;
+    }
+
+    for slot in slots {
+        if selected == entry {
+            cod::pixel('-', 1, y);
+            preview = slot.info.as_ref().ok();
+        }
+
+        palette::fg(if slot.backup.is_some() { 8 } else { 3 });
+        cod::blit(label(slot), 3, y);
+
+        match &slot.info {
+            Ok(info) => {
+                palette::fg(7);
+                cod::blit(
+                    format!(
+                        "{}, turn {}, {}/{} hp",
+                        info.sector, info.turn, info.health, info.max_health
+                    ),
+                    5,
+                    y + 1,
+                );
+                let mode = match info.mode {
+                    GameMode::Normal => String::new(),
+                    mode => format!(" {}", mode.name()),
+                };
+                cod::blit(
+                    format!(
+                        "{} items, {}{mode}, {}",
+                        info.items,
+                        info.difficulty.name(),
+                        ago(slot.modified)
+                    ),
+                    5,
+                    y + 2,
+                );
+            }
+            Err(e) => {
+                palette::fg(1);
+                cod::blit(e.to_string(), 5, y + 1);
+            }
+        }
+        cod::color::de_fg();
+
+        y += 4;
+        entry += 1;
+    }
+
+    y -= 1;
+    cod::orth_line('|', 0, 1, 0, y).unwrap();
+    cod::pixel('+', 0, y);
+
+    if let Some(info) = preview {
+        draw_preview(world, info);
+    }
+
+    let bottom = y.max(HEIGHT as u32 + 2) + 1;
+    palette::fg(8);
+    let help = match mode {
+        Mode::Save => "Enter: save  r: rename  x: delete  q: back",
+        Mode::Load => "Enter: load  r: rename  x: delete  q: back",
+    };
+    cod::blit(help, 2, bottom);
+    cod::color::de();
+
+    cod::goto::bot();
+    cod::flush();
+
+    bottom + 1
+}
+
+/// Draws a small map of the sector the save is in, with the player on it.
+fn draw_preview(world: &World, info: &SaveInfo) {
+    let Some(sector) = world.map.get_sector(&info.sector) else {
+        return;
+    };
+
+    let theme = theme::get();
+    for (y, row) in sector.tiles().iter().enumerate() {
+        for (x, tile) in row.iter().enumerate() {
+            palette::fg(tile.kind.color());
+            palette::bg(tile.kind.faded_color());
+            cod::pixel(
+                theme.tile_char(tile.kind),
+                PREVIEW_X + x as u32,
+                1 + y as u32,
+            );
+        }
+    }
+
+    let (x, y) = info.position;
+    cod::color::de_bg();
+    palette::fg(140);
+    cod::pixel('G', PREVIEW_X + x, 1 + y);
+
+    cod::color::de();
+    cod::blit(&info.sector, PREVIEW_X, 2 + HEIGHT as u32);
+}
+
+fn prompt(msg: &str, y: u32) -> Option<String> {
+    cod::goto::pos(0, y);
+    cod::clear::line();
+    palette::fg(2);
+    print!("{msg}");
+    cod::color::de_fg();
+    cod::flush();
+
+    let name = term::line()?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(['/', '\\', '.']) {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+fn confirm(msg: &str, y: u32) -> bool {
+    cod::goto::pos(0, y);
+    cod::clear::line();
+    palette::fg(1);
+    print!("{msg}");
+    cod::color::de_fg();
+    cod::flush();
+
+    matches!(term::key(), Some(Key::Char('y' | 'Y')))
+}
+
+fn message(msg: &str, y: u32) {
+    cod::goto::pos(0, y);
+    cod::clear::line();
+    palette::fg(1);
+    print!("{msg}");
+    cod::color::de_fg();
+    cod::flush();
+    term::key();
+}
+
+/// Roughly how long ago `time` was, e.g. "5m ago".
+fn ago(time: Option<SystemTime>) -> String {
+    let Some(secs) = time
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .map(|d| d.as_secs())
+    else {
+        return "unknown time".to_string();
+    };
+
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
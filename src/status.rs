@@ -0,0 +1,74 @@
+use std::mem;
+
+use serde::{Deserialize, Serialize};
+
+use crate::item::Buff;
+use crate::player::{Player, Stat};
+
+/// A status effect on the player that wears off after a while.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Status {
+    pub kind: StatusKind,
+    /// How many more turns it lasts.
+    pub turns: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StatusKind {
+    /// Deals this much damage every turn.
+    Poison(u32),
+    /// Heals this much every turn.
+    Regeneration(u32),
+    /// Given for eating your fill.
+    WellFed,
+    /// Given for as long as the player is out of water.
+    Dehydrated,
+}
+
+impl StatusKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Poison(_) => "Poisoned",
+            Self::Regeneration(_) => "Regenerating",
+            Self::WellFed => "Well-fed",
+            Self::Dehydrated => "Dehydrated",
+        }
+    }
+
+    pub fn color(&self) -> u8 {
+        match self {
+            Self::Poison(_) => 34,
+            Self::Regeneration(_) => 198,
+            Self::WellFed => 223,
+            Self::Dehydrated => 166,
+        }
+    }
+
+    /// Whether the two are the same kind of status, whatever their
+    /// strength.
+    pub fn same(&self, other: &StatusKind) -> bool {
+        mem::discriminant(self) == mem::discriminant(other)
+    }
+
+    /// The buff the status gives while it's active, and whether it's
+    /// a debuff.
+    fn buff(&self) -> Option<(Buff, bool)> {
+        match self {
+            Self::WellFed => Some((Buff::Percent(Stat::MaxHealth, 20), false)),
+            Self::Dehydrated => Some((Buff::Percent(Stat::Damage, 25), true)),
+            Self::Poison(_) | Self::Regeneration(_) => None,
+        }
+    }
+
+    pub fn apply(&self, player: &mut Player) {
+        if let Some((buff, debuff)) = self.buff() {
+            buff.apply(player, debuff);
+        }
+    }
+
+    pub fn unapply(&self, player: &mut Player) {
+        if let Some((buff, debuff)) = self.buff() {
+            buff.apply(player, !debuff);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock};
+
+use cod::Key;
+use console::Term;
+use signal_hook::consts::{SIGINT, SIGWINCH};
+
+static RESIZED: LazyLock<Arc<AtomicBool>> = LazyLock::new(Arc::default);
+static INTERRUPTED: LazyLock<Arc<AtomicBool>> = LazyLock::new(Arc::default);
+/// Cleared while a key or line is being read. The rest of the time,
+/// SIGINT quits straight away like it normally would.
+static IDLE: LazyLock<Arc<AtomicBool>> = LazyLock::new(|| Arc::new(AtomicBool::new(true)));
+
+/// Starts listening for the terminal being resized.
+///
+/// A resize interrupts any key being read, which `console` takes to
+/// mean Ctrl+C was pressed and raises SIGINT itself. So SIGINT is caught
+/// during reads too, and `key` tells the two apart.
+pub fn init() -> io::Result<()> {
+    signal_hook::flag::register(SIGWINCH, Arc::clone(&RESIZED))?;
+    signal_hook::flag::register_conditional_default(SIGINT, Arc::clone(&IDLE))?;
+    signal_hook::flag::register(SIGINT, Arc::clone(&INTERRUPTED))?;
+    Ok(())
+}
+
+/// The terminal's width and height, in cells.
+pub fn size() -> (u32, u32) {
+    let (rows, cols) = Term::stdout().size();
+    (cols as u32, rows as u32)
+}
+
+/// Whether the terminal was resized since this was last called.
+pub fn resized() -> bool {
+    RESIZED.swap(false, Ordering::Relaxed)
+}
+
+/// Reads a single key. Returns `None` if reading failed, or if the
+/// terminal was resized while waiting (see `resized`).
+pub fn key() -> Option<Key> {
+    reading(cod::read::key)
+}
+
+/// Reads a line, the same way as `cod::read::line`.
+pub fn line() -> Option<String> {
+    reading(cod::read::line)
+}
+
+/// Runs `read` with SIGINT caught, then quits the way an uncaught Ctrl+C
+/// would if one came in that wasn't really a resize.
+fn reading<T>(read: impl FnOnce() -> T) -> T {
+    IDLE.store(false, Ordering::Relaxed);
+    let value = read();
+    IDLE.store(true, Ordering::Relaxed);
+
+    if INTERRUPTED.swap(false, Ordering::Relaxed) && !RESIZED.load(Ordering::Relaxed) {
+        let _ = signal_hook::low_level::emulate_default_handler(SIGINT);
+    }
+    value
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::config::ConfigError;
+use crate::entity::EntityKind;
+use crate::map::TileKind;
+use crate::player::constants::CHARACTER;
+use crate::render::{self, Color};
+
+pub const BUILTIN: [(&str, &str); 4] = [
+    ("ascii", include_str!("../themes/ascii.json")),
+    ("unicode", include_str!("../themes/unicode.json")),
+    ("cp437", include_str!("../themes/cp437.json")),
+    ("emoji", include_str!("../themes/emoji.json")),
+];
+
+/// Something on the map that isn't a tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Sprite {
+    Player,
+    Food,
+    Enemy,
+    Boss,
+    Item,
+    Npc,
+}
+
+impl Sprite {
+    pub fn of(kind: &EntityKind) -> Self {
+        match kind {
+            EntityKind::Food { .. } => Self::Food,
+            EntityKind::Enemy { .. } => Self::Enemy,
+            EntityKind::Boss { .. } => Self::Boss,
+            EntityKind::Item(_) => Self::Item,
+            EntityKind::Npc { .. } => Self::Npc,
+        }
+    }
+
+    fn glyph(&self) -> char {
+        match self {
+            Self::Player => CHARACTER,
+            Self::Food => '+',
+            Self::Enemy => '!',
+            Self::Boss => '#',
+            Self::Item => '?',
+            Self::Npc => '&',
+        }
+    }
+
+    fn color(&self) -> Color {
+        Color::Indexed(match self {
+            Self::Player => 140,
+            Self::Food => 108,
+            Self::Enemy => 210,
+            Self::Boss => 136,
+            Self::Item => 56,
+            Self::Npc => 79,
+        })
+    }
+}
+
+/// How a tile looks. Its glyph is two columns wide, either two narrow
+/// characters or one wide one; the background is a faded version of
+/// its color.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TileLook {
+    pub glyph: Option<String>,
+    pub color: Option<(u8, u8, u8)>,
+}
+
+/// How an entity looks. A wide glyph covers the whole tile.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SpriteLook {
+    pub glyph: Option<char>,
+    pub color: Option<Color>,
+}
+
+/// Glyphs and colors to draw the map with. Anything left out looks
+/// the way the map files and enemy catalog say it does.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub tiles: HashMap<TileKind, TileLook>,
+    pub sprites: HashMap<Sprite, SpriteLook>,
+    /// Looks for specific kinds of enemy, by archetype id.
+    pub enemies: HashMap<String, SpriteLook>,
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+static NO_LOOK: SpriteLook = SpriteLook {
+    glyph: None,
+    color: None,
+};
+
+/// The theme the game is drawn with. Until one is set, everything
+/// looks the way it's defined.
+pub fn get() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+/// Picks the theme to draw the game with. Only the first call has any
+/// effect.
+pub fn set(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+/// Loads one of the built-in themes by name, or else the theme file at
+/// `name`.
+pub fn load(name: &str) -> Result<Theme, ConfigError> {
+    let path = PathBuf::from(name);
+    let json = match BUILTIN.iter().find(|(n, _)| *n == name) {
+        Some((_, json)) => json.to_string(),
+        None => fs::read_to_string(&path).map_err(|e| ConfigError::Io(path.clone(), e))?,
+    };
+
+    let theme: Theme =
+        serde_json::from_str(&json).map_err(|e| ConfigError::Parse(path.clone(), e))?;
+
+    for (kind, look) in &theme.tiles {
+        if let Some(glyph) = &look.glyph {
+            if render::width(glyph) != 2 {
+                return Err(ConfigError::Invalid(
+                    path,
+                    format!("the {kind:?} glyph `{glyph}` isn't two columns wide"),
+                ));
+            }
+        }
+    }
+
+    Ok(theme)
+}
+
+impl Theme {
+    pub fn tile_glyph(&self, kind: TileKind) -> String {
+        match self.tiles.get(&kind).and_then(|l| l.glyph.clone()) {
+            Some(glyph) => glyph,
+            None => format!("{0}{0}", kind as u8 as char),
+        }
+    }
+
+    /// A single column to show the tile with, e.g. in the key. Falls back
+    /// to the map file character if the glyph doesn't start with one.
+    pub fn tile_char(&self, kind: TileKind) -> char {
+        self.tile_glyph(kind)
+            .chars()
+            .next()
+            .filter(|&ch| render::char_width(ch) == 1)
+            .unwrap_or(kind as u8 as char)
+    }
+
+    pub fn tile_color(&self, kind: TileKind) -> Option<(u8, u8, u8)> {
+        self.tiles.get(&kind).and_then(|l| l.color)
+    }
+
+    pub fn glyph(&self, sprite: Sprite) -> char {
+        self.sprites
+            .get(&sprite)
+            .and_then(|l| l.glyph)
+            .unwrap_or(sprite.glyph())
+    }
+
+    pub fn color(&self, sprite: Sprite) -> Color {
+        self.sprites
+            .get(&sprite)
+            .and_then(|l| l.color)
+            .unwrap_or(sprite.color())
+    }
+
+    /// How the enemy archetype `id` looks, over the catalog's sprite and
+    /// color.
+    pub fn enemy(&self, id: &str) -> &SpriteLook {
+        self.enemies.get(id).unwrap_or(&NO_LOOK)
+    }
+}
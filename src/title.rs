@@ -1,31 +1,58 @@
+use crate::palette;
+use crate::render;
+
 const TITLE: &str = include_str!("../title/title.txt");
 const LOGO: &str = include_str!("../title/logo.txt");
 const LOGO_SHADING: &str = include_str!("../title/logo_shading.txt");
 
-pub fn draw(x: u32, y: u32) {
+/// Draws the title art with a menu below it. Disabled entries are
+/// greyed out.
+pub fn draw(x: u32, y: u32, entries: &[(&str, bool)], selected: usize) {
     cod::clear::all();
+    render::invalidate();
     title(x, y);
     logo(x + 24, y + 2);
 
+    let width = entries.iter().map(|(e, _)| e.len()).max().unwrap_or(0) + 4;
+
+    for (i, (entry, enabled)) in entries.iter().enumerate() {
+        let y = y + 6 + i as u32;
+
+        cod::color::de();
+        cod::goto::pos(x + 2, y);
+        print!("{}", if i == 0 { "=\\" } else { " |" });
+        cod::goto::pos(x + 5 + width as u32, y);
+        print!("|");
+
+        if i == selected {
+            cod::pixel('-', x + 5, y);
+        }
+
+        palette::fg(match (enabled, i == selected) {
+            (false, _) => 8,
+            (true, true) => 2,
+            (true, false) => 7,
+        });
+        cod::blit(*entry, x + 7, y);
+    }
+
     cod::color::de();
-    cod::goto::pos(x + 2, y + 6);
-    println!("=\\ Press any    |");
-    cod::goto::right(x + 2);
-    println!(" | key to begin |");
+    cod::goto::bot();
+    cod::flush();
 }
 
 fn title(x: u32, y: u32) {
     let title = TITLE.replace('#', "\t");
 
-    cod::color::fg(238);
+    palette::fg(238);
     cod::blit_transparent(&title, x + 2, y + 1);
     cod::color::de_fg();
     cod::blit_transparent(&title, x, y);
 }
 
 fn logo(x: u32, y: u32) {
-    cod::color::fg(28);
+    palette::fg(28);
     cod::blit(LOGO, x, y);
-    cod::color::fg(22);
+    palette::fg(22);
     cod::blit_transparent(LOGO_SHADING, x, y);
 }
@@ -1,33 +1,87 @@
 use std::fmt::Display;
 
-use rand::{thread_rng, Rng};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
-use crate::{good, bad};
+use cod::BoxChars;
+
+use crate::action::Action;
+use crate::bestiary::{Bestiary, Loot};
+use crate::boss;
 use crate::difficulty::Difficulty;
 use crate::entity::{Entity, EntityKind};
-use crate::input::{self, TurnResult, GoodResult};
+use crate::good;
+use crate::input::{BadResult, GoodResult, TurnResult, UpdateResult};
+use crate::item::{Effect, Slot};
+use crate::log::Log;
 use crate::map::{Direction, Map, TileKind, HEIGHT, WIDTH};
-use crate::player::{constants::*, Player};
-use crate::world_map::sectors;
+use crate::player::{constants::*, Player, Stat};
+use crate::render;
+use crate::replay::Replay;
+use crate::status::StatusKind;
+use crate::theme::{self, Sprite};
+use crate::world_map::WorldDef;
+
+/// How the run treats saving.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    #[default]
+    Normal,
+    /// The run is saved after every turn and can't be saved or loaded
+    /// by hand. Loading the save deletes it, and dying wipes it.
+    Ironman,
+}
+
+impl GameMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Ironman => "ironman",
+        }
+    }
+}
+
+/// The smallest terminal the map, key and message log fit in.
+pub const MIN_SIZE: (u32, u32) = (WIDTH as u32 * 2 + 1, HEIGHT as u32 + 5 + LOG_LINES);
+/// How many of the latest messages are shown below the key.
+const LOG_LINES: u32 = 3;
+/// How much room the inventory needs beside the map. On terminals too
+/// narrow for it, it goes below the key instead.
+const SIDEBAR_WIDTH: u32 = 24;
 
 #[derive(Debug)]
 pub struct World {
     pub map: Map,
     pub player: Player,
     pub entities: Vec<Entity>,
-    pub despawned: Vec<(&'static str, u32)>,
+    pub despawned: Vec<(String, u32)>,
     pub turn: u32,
     pub difficulty: Difficulty,
+    pub mode: GameMode,
+    /// Whether the player and enemies can move diagonally.
+    pub diagonal: bool,
+    /// The seed `rng` was created from, so a run can be reproduced.
+    pub seed: u64,
+    pub rng: ChaCha8Rng,
+    /// If set, every action passed to `update` is recorded here.
+    pub recording: Option<Replay>,
+    /// Every kind of enemy that can spawn in the world.
+    pub bestiary: Bestiary,
+    /// Everything the player's been told this session.
+    pub log: Log,
+    /// The world as it was defined, before anything happened to it.
+    origin: WorldDef,
 }
 
 impl World {
-    pub fn new(player_x: u32, player_y: u32) -> Self {
-        let (entities, map) = Map::new(sectors(), "start");
+    pub fn new(def: WorldDef, seed: u64) -> Self {
+        let (entities, map) = Map::new(def.sectors.clone(), &def.start);
+        let (x, y) = def.spawn;
         Self {
             map,
             player: Player {
-                x: player_x,
-                y: player_y,
+                x,
+                y,
                 hunger: 0,
                 thirst: 0,
                 hunger_cap: INITIAL_HUNGER_CAP,
@@ -35,19 +89,43 @@ impl World {
                 health: 10,
                 max_health: 10,
                 damage: 1,
+                armor: 0,
+                dodge: 0,
+                vision: BASE_VISION,
+                speed: 0,
+                bonus: Default::default(),
+                percent: Default::default(),
+                statuses: Vec::new(),
                 inventory: Vec::new(),
+                equipped: Default::default(),
             },
             entities,
             despawned: Vec::new(),
             turn: 0,
             difficulty: Difficulty::normal(),
+            mode: GameMode::Normal,
+            diagonal: false,
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            recording: None,
+            bestiary: def.bestiary.clone(),
+            log: Log::default(),
+            origin: def,
         }
     }
 
+    /// Resets every sector to how it was defined, discarding tile
+    /// changes and entity state.
+    pub fn reset_map(&mut self) {
+        let (entities, map) = Map::new(self.origin.sectors.clone(), &self.origin.start);
+        self.map = map;
+        self.entities = entities;
+    }
+
     pub fn despawn(&mut self, i: usize) {
         let entity = self.entities.remove(i);
         if let Some(id) = entity.id() {
-            self.despawned.push((self.map.sector().id, id));
+            self.despawned.push((self.map.sector().id.clone(), id));
         }
     }
 
@@ -71,122 +149,431 @@ impl World {
         1.0 + self.turn as f32 / 15.0
     }
 
+    /// Writes a message over the bottom border straight away, without
+    /// logging it. For when nothing else will be drawn before the next
+    /// key, like on death.
     pub fn draw_message(&self, msg: impl Display, color: u8) {
-        cod::goto::pos(0, HEIGHT as u32);
-        //cod::clear::line();
-        cod::color::de();
-        print!("{}/  ", "-".repeat(WIDTH * 2));
+        render::de();
+        render::blit(format!("{}/  ", "-".repeat(WIDTH * 2)), 0, HEIGHT as u32);
 
-        cod::goto::pos(1, HEIGHT as u32);
-        cod::color::fg(color);
-        print!("{msg}");
-        cod::goto::bot();
-        cod::flush();
+        render::fg(color);
+        render::blit(msg.to_string(), 1, HEIGHT as u32);
+        render::flush();
     }
 
-    pub fn draw_result(&self, res: GoodResult) {
-        self.draw_key();
+    /// Adds a message to the log, stamped with the current turn.
+    pub fn message(&mut self, msg: impl Display, color: u8) {
+        self.log.push(self.turn, msg, color);
+    }
+
+    /// Logs what the player should be told about `res`, if anything.
+    pub fn log_result(&mut self, res: &GoodResult) {
         match res {
-            GoodResult::NoKey => self.draw_message("Please press a key", 1),
-            GoodResult::InvalidMove(_) => self.draw_message("You can't move there", 1),
-            GoodResult::WaterMove => self.draw_message("You drank your fill", 2),
-            GoodResult::InvalidKey(_) => self.draw_message("That's not a valid key", 1),
-            GoodResult::Fight(dmg, hp) => self.draw_message(
+            GoodResult::NoKey => self.message("Please press a key", 1),
+            GoodResult::InvalidMove(_) => self.message("You can't move there", 1),
+            GoodResult::WaterMove => self.message("You drank your fill", 2),
+            GoodResult::InvalidKey(_) => self.message("That's not a valid key", 1),
+            GoodResult::Fight(dmg, hp) => self.message(
                 format!(
                     "You dealt {}, they dealt {dmg} and are at {hp}",
-                    self.player.damage
+                    self.player.damage()
+                ),
+                3,
+            ),
+            GoodResult::Dodged(hp) => self.message(
+                format!(
+                    "You dealt {} and dodged their hit, they're at {hp}",
+                    self.player.damage()
                 ),
                 3,
             ),
-            GoodResult::WonFight(upgrade) => {
-                if upgrade {
-                    self.draw_message("You won and got an upgrade!", 2)
+            GoodResult::WonFight(upgrade, loot) => {
+                let msg = if *upgrade {
+                    "You won and got an upgrade!"
+                } else {
+                    "You killed the enemy!"
+                };
+                if loot.is_empty() {
+                    self.message(msg, 2)
                 } else {
-                    self.draw_message("You killed the enemy!", 2)
+                    self.message(format!("{msg} They dropped {}", loot.join(", ")), 2)
                 }
             }
             GoodResult::DefeatedBoss(_id) => {
-                self.draw_message("You killed the boss!", 2);
+                self.message("You killed the boss!", 2);
+            }
+            GoodResult::BossPhase => self.message("The boss grows more dangerous!", 1),
+            GoodResult::BossTelegraph => self.message("The boss is winding up an attack!", 3),
+            GoodResult::BossHit(dmg) => {
+                self.message(format!("The boss's attack hit you for {dmg}!"), 1)
             }
-            GoodResult::Ate(food) => {
-                self.draw_message(format!("You ate {food} food and healed 2"), 2)
+            GoodResult::BossMissed => self.message("You dodged the boss's attack", 2),
+            GoodResult::Ate(food) => self.message(format!("You ate {food} food and healed 2"), 2),
+            GoodResult::ThirstDamage => self.message("You took 1 damage from thirst!", 1),
+            GoodResult::PoisonDamage(dmg) => {
+                self.message(format!("You took {dmg} damage from poison!"), 1)
             }
+            GoodResult::StatusEnded(kind) => self.message(
+                format!("You're no longer {}", kind.name().to_lowercase()),
+                8,
+            ),
+            GoodResult::Dialogue(speech) => self.message(speech.replace('\n', " "), 7),
             GoodResult::Saved => {
-                self.draw_message("Saved!", 2);
+                self.message("Saved!", 2);
+            }
+            GoodResult::Autosaved => {
+                self.message("Autosaved", 8);
             }
             GoodResult::Loaded => {
-                self.draw_message("Loaded!", 2);
+                self.message("Loaded!", 2);
+            }
+            GoodResult::Dropped(name) => self.message(format!("You dropped the {name}"), 2),
+            GoodResult::Used(name) => self.message(format!("You used the {name}"), 2),
+            GoodResult::Equipped(name) => self.message(format!("You equipped the {name}"), 2),
+            GoodResult::Unequipped(name) => self.message(format!("You took off the {name}"), 2),
+            GoodResult::NotEquippable(name) => {
+                self.message(format!("The {name} can't be equipped"), 1)
+            }
+            GoodResult::InventoryFull => self.message("You can't carry any more", 1),
+            GoodResult::NotUsable(name) => self.message(format!("The {name} can't be used"), 1),
+            GoodResult::Bombed(hit, loot) => {
+                let msg = format!("The bomb hit {hit} enemies");
+                if loot.is_empty() {
+                    self.message(msg, 208)
+                } else {
+                    self.message(format!("{msg}. They dropped {}", loot.join(", ")), 208)
+                }
+            }
+            GoodResult::Ironman => {
+                self.message("You can't save or load in ironman mode", 1);
             }
             _ => {}
         }
     }
 
-    pub fn update(&mut self) -> TurnResult {
-        let mut res = input::handle(self)?;
-        while res != GoodResult::Ok {
-            self.draw(0, 0);
-            self.draw_key();
-            self.draw_result(res);
-            res = input::handle(self)?;
+    /// Draws a speech box in the top-left corner.
+    pub fn draw_dialogue(&self, speech: &str) {
+        let lines = speech.lines();
+        let (width, height) = lines.fold((0, 0), |(w, h), l| (w.max(l.len()), h + 1));
+
+        render::de();
+        render::clear_rect(0, 0, width as u32 + 1, height + 1);
+        render::rect_lines(
+            BoxChars {
+                horizontal: '-',
+                vertical: '|',
+                corner: '+',
+            },
+            0,
+            0,
+            width as u32 + 1,
+            height + 1,
+        );
+
+        render::blit(speech, 1, 1);
+        render::flush();
+    }
+
+    /// Applies the player's action. If it took up their turn (i.e. it
+    /// resulted in `GoodResult::Ok`), the rest of the world then gets to
+    /// move as well.
+    ///
+    /// Returns everything noteworthy that happened, in order.
+    pub fn update(&mut self, action: Action) -> UpdateResult {
+        if let Some(replay) = &mut self.recording {
+            replay.record(self.turn, &action);
         }
 
+        let res = match action {
+            Action::Move(direction) => {
+                let res = self.go(direction)?;
+
+                // fast players sometimes get a move for free
+                let speed = self.player.stat(Stat::Speed);
+                if res == GoodResult::Ok && speed > 0 && self.rng.gen_range(0..100) < speed {
+                    return Ok(Vec::new());
+                }
+                res
+            }
+            Action::Interact => self.interact()?,
+            Action::Drop(i) => self.drop_item(i),
+            Action::Use(i) => self.use_item(i),
+            Action::Equip(i) => self.equip(i),
+            Action::Unequip(slot) => self.unequip(slot),
+            Action::SetDifficulty(difficulty) => {
+                self.difficulty = difficulty;
+                GoodResult::Menued
+            }
+            Action::Quit => return Err(BadResult::Quit),
+        };
+
+        if res != GoodResult::Ok {
+            return Ok(vec![res]);
+        }
+
+        let mut events = Vec::new();
+        self.tick(&mut events)?;
+        Ok(events)
+    }
+
+    fn tick(&mut self, events: &mut Vec<GoodResult>) -> Result<(), BadResult> {
         self.turn += 1;
 
-        if self.turn % HUNGER_INTERVAL == 0 && self.map.sector().do_survival {
+        if self.turn.is_multiple_of(HUNGER_INTERVAL) && self.map.sector().do_survival {
             self.player.hunger += 1;
         }
 
-        if self.turn % THIRST_INTERVAL == 0
-            && self.player.thirst <= self.player.thirst_cap
+        let thirst_cap = self.player.stat(Stat::ThirstCap);
+        if self.turn.is_multiple_of(THIRST_INTERVAL)
+            && self.player.thirst <= thirst_cap
             && self.map.sector().do_survival
         {
             self.player.thirst += 1;
         }
 
-        if self.player.thirst > self.player.thirst_cap {
+        // lasts a turn longer than it's refreshed for, so it wears off
+        // the turn after drinking
+        if self.player.thirst >= thirst_cap {
+            self.player.add_status(StatusKind::Dehydrated, 2);
+        }
+
+        self.tick_statuses(events)?;
+
+        if self.player.thirst > thirst_cap {
             self.player.health = self.player.health.saturating_sub(1);
             if self.player.health == 0 {
-                return bad!(ThirstDeath);
+                return Err(BadResult::ThirstDeath);
+            }
+
+            events.push(GoodResult::ThirstDamage);
+        } else if self.player.hunger > thirst_cap {
+            return Err(BadResult::HungerDeath);
+        }
+
+        // entities spawned during this loop don't get to act until next turn
+        for i in 0..self.entities.len() {
+            let mut entity = self.entities[i].clone();
+            let mut res = entity.ai(self)?;
+            if !entity.alive {
+                res = self.loot(res, &entity);
+            }
+            self.inflict(&res, &entity);
+            if res != GoodResult::Ok {
+                events.push(res);
             }
 
-            self.draw_message("You took 1 damage from thirst!", 1);
-        } else if self.player.hunger > self.player.thirst_cap {
-            return bad!(HungerDeath);
+            self.entities[i] = entity;
+        }
+
+        let mut i = 0;
+        while i < self.entities.len() {
+            if self.entities[i].alive {
+                i += 1;
+            } else {
+                self.despawn(i);
+            }
         }
 
-        let mut kill = Vec::new();
-        let mut entities = self.entities.clone();
-        for (i, entity) in entities.iter_mut().enumerate() {
-            let r = entity.ai(self)?;
-            if r != GoodResult::Ok {
-                res = r;
-                self.draw_result(res);
+        let spawned = Entity::spawn_random(self);
+        self.entities.extend(spawned);
+
+        Ok(())
+    }
+
+    /// Lets each of the player's status effects take effect, ending the
+    /// ones that have run out.
+    fn tick_statuses(&mut self, events: &mut Vec<GoodResult>) -> Result<(), BadResult> {
+        let mut i = 0;
+        while i < self.player.statuses.len() {
+            let status = &mut self.player.statuses[i];
+            status.turns = status.turns.saturating_sub(1);
+            let (kind, turns) = (status.kind, status.turns);
+
+            match kind {
+                StatusKind::Poison(damage) => {
+                    if damage >= self.player.health {
+                        self.player.health = 0;
+                        return Err(BadResult::PoisonDeath);
+                    }
+
+                    self.player.health -= damage;
+                    events.push(GoodResult::PoisonDamage(damage));
+                }
+                StatusKind::Regeneration(amount) => self.player.heal(amount),
+                StatusKind::WellFed | StatusKind::Dehydrated => {}
             }
-            self.draw(0, 0);
 
-            if entity.alive {
-                self.entities[i].x = entity.x;
-                self.entities[i].y = entity.y;
+            if turns == 0 {
+                self.player.end_status(i);
+                events.push(GoodResult::StatusEnded(kind));
             } else {
-                kill.push(i);
+                i += 1;
             }
         }
 
-        for (o, i) in kill.into_iter().enumerate() {
-            self.despawn(i - o);
+        Ok(())
+    }
+
+    /// Gives the player whatever status `enemy` inflicts, if it landed a
+    /// hit.
+    fn inflict(&mut self, res: &GoodResult, enemy: &Entity) {
+        if !matches!(res, GoodResult::Fight(..)) {
+            return;
+        }
+
+        if let Some(status) = enemy
+            .kind
+            .archetype(&self.bestiary)
+            .and_then(|a| a.inflicts)
+        {
+            self.player.add_status(status.kind, status.turns);
         }
+    }
 
-        if let Some(e) = Entity::spawn_random(self) {
-            entities.push(e);
+    fn drop_item(&mut self, i: usize) -> GoodResult {
+        if i >= self.player.inventory.len() {
+            return GoodResult::Menued;
         }
 
-        self.entities = entities;
+        let item = self.player.remove_item(i).unwrap();
+        let name = item.name.clone();
+
+        // it's back on the map, so it's no longer despawned
+        if item.id != 0 {
+            self.despawned.retain(|(_, id)| *id != item.id);
+        }
+
+        let (x, y) = (self.player.x, self.player.y);
+        self.entities
+            .push(Entity::new(x, y, EntityKind::Item(item), true));
+        GoodResult::Dropped(name)
+    }
+
+    fn equip(&mut self, i: usize) -> GoodResult {
+        let Some(item) = self.player.inventory.get(i) else {
+            return GoodResult::Menued;
+        };
+
+        let name = item.name.clone();
+        if self.player.equip(i) {
+            GoodResult::Equipped(name)
+        } else {
+            GoodResult::NotEquippable(name)
+        }
+    }
+
+    fn unequip(&mut self, slot: Slot) -> GoodResult {
+        let Some(item) = self.player.equipped(slot) else {
+            return GoodResult::Menued;
+        };
+
+        let name = item.name.clone();
+        if self.player.unequip(slot) {
+            GoodResult::Unequipped(name)
+        } else {
+            GoodResult::InventoryFull
+        }
+    }
+
+    /// Uses one charge of the consumable at `i`, throwing it away once
+    /// it's used up.
+    fn use_item(&mut self, i: usize) -> GoodResult {
+        let Some(item) = self.player.inventory.get_mut(i) else {
+            return GoodResult::Menued;
+        };
+        let Some(consumable) = &mut item.consumable else {
+            return GoodResult::NotUsable(item.name.clone());
+        };
+
+        let effect = consumable.effect;
+        let name = item.name.clone();
+        consumable.used += 1;
+        if consumable.used >= consumable.uses {
+            consumable.used = 0;
+            self.player.remove_item(i);
+        }
+
+        let player = &mut self.player;
+        match effect {
+            Effect::Food(n) => player.eat(n),
+            Effect::Water(n) => player.thirst = player.thirst.saturating_sub(n),
+            Effect::Heal(n) => player.heal(n),
+            Effect::Bomb { radius, damage } => return self.bomb(radius, damage),
+            Effect::Status { status, turns } => player.add_status(status, turns),
+        }
+
+        GoodResult::Used(name)
+    }
+
+    fn bomb(&mut self, radius: u32, damage: u32) -> GoodResult {
+        let (px, py) = (self.player.x, self.player.y);
+        let mut hit = 0;
+        let mut killed = Vec::new();
+        for (i, entity) in self.entities.iter_mut().enumerate() {
+            let EntityKind::Enemy { health, .. } = &mut entity.kind else {
+                continue;
+            };
+            if entity.x.abs_diff(px).max(entity.y.abs_diff(py)) > radius {
+                continue;
+            }
+
+            hit += 1;
+            *health = health.saturating_sub(damage);
+            if *health == 0 {
+                entity.alive = false;
+                killed.push(i);
+            }
+        }
+
+        let mut loot = Vec::new();
+        for (n, i) in killed.into_iter().enumerate() {
+            let enemy = self.entities[i - n].clone();
+            self.despawn(i - n);
+            loot.extend(self.drop_loot(&enemy));
+        }
+
+        GoodResult::Bombed(hit, loot)
+    }
+
+    /// Rolls the loot table of a freshly killed enemy.
+    fn loot(&mut self, res: GoodResult, enemy: &Entity) -> GoodResult {
+        let GoodResult::WonFight(upgrade, mut names) = res else {
+            return res;
+        };
+
+        names.extend(self.drop_loot(enemy));
+        GoodResult::WonFight(upgrade, names)
+    }
 
-        good!()
+    /// Leaves whatever drops from `enemy`'s loot table on its tile.
+    /// Returns the names of the drops.
+    fn drop_loot(&mut self, enemy: &Entity) -> Vec<String> {
+        let mut names = Vec::new();
+        let Some(archetype) = enemy.kind.archetype(&self.bestiary) else {
+            return names;
+        };
+
+        let loot_mul = (self.difficulty * self.map.sector().difficulty).loot_mul;
+        for drop in archetype.loot.clone() {
+            if self.rng.gen::<f32>() > drop.chance * loot_mul {
+                continue;
+            }
+
+            names.push(drop.loot.name().to_string());
+            let kind = match drop.loot {
+                Loot::Food(food) => EntityKind::Food { food },
+                Loot::Item(item) => EntityKind::Item(item),
+            };
+            self.entities
+                .push(Entity::new(enemy.x, enemy.y, kind, false));
+        }
+
+        names
     }
 
     pub fn interact(&mut self) -> TurnResult {
         let mut kill = None;
+        let mut fought = None;
         let mut res = good!();
         if let Some((i, entity)) = &mut self
             .entities
@@ -194,7 +581,8 @@ impl World {
             .enumerate()
             .find(|(_, e)| e.x == self.player.x && e.y == self.player.y)
         {
-            res = entity.interact(&mut self.player, &mut self.map)?;
+            res = entity.interact(&mut self.player, &mut self.map, &mut self.rng)?;
+            fought = Some(entity.clone());
 
             if !entity.alive {
                 kill = Some(*i);
@@ -204,15 +592,19 @@ impl World {
                 self.despawn(i);
             }
         } else {
-            self.player.health = (self.player.health + self.turn % 2).min(self.player.max_health);
+            self.player.heal(self.turn % 2);
+        }
+
+        if matches!(res, GoodResult::WonFight(..)) && self.rng.gen::<f32>() <= UPGRADE_CHANCE {
+            self.player.damage += 1;
+            res = good!(WonFight, true, Vec::new());
         }
 
-        if matches!(res, GoodResult::WonFight(_)) {
-            let mut rng = thread_rng();
-            if rng.gen::<f32>() <= UPGRADE_CHANCE {
-                self.player.damage += 1;
-                res = good!(WonFight, true);
+        if let Some(enemy) = fought {
+            if !enemy.alive {
+                res = self.loot(res, &enemy);
             }
+            self.inflict(&res, &enemy);
         }
 
         Ok(res)
@@ -221,57 +613,164 @@ impl World {
     pub fn draw(&self, x: u32, y: u32) {
         self.map.draw(x, y);
         self.draw_key();
-        self.draw_inventory_side(x, y);
+        self.log.draw(
+            x,
+            y + MIN_SIZE.1 - LOG_LINES,
+            WIDTH as u32 * 2 + 1,
+            LOG_LINES,
+            0,
+        );
+
+        let side = x + WIDTH as u32 * 2 + 2;
+        if render::size().0 >= side + SIDEBAR_WIDTH {
+            self.draw_inventory_side(side, y);
+        } else {
+            self.draw_inventory_side(x, y + MIN_SIZE.1);
+        }
 
+        let vision = self.player.stat(Stat::Vision);
         for entity in &self.entities {
-            entity.draw(x, y);
+            let distance = entity
+                .x
+                .abs_diff(self.player.x)
+                .max(entity.y.abs_diff(self.player.y));
+            if distance <= vision {
+                entity.draw(x, y, &self.bestiary);
+            }
         }
 
-        cod::color::fg(140);
-        cod::color::de_bg();
-        cod::pixel(CHARACTER, self.player.x * 2 + x, self.player.y + y);
-        cod::color::de_fg();
+        let theme = theme::get();
+        render::fg(theme.color(Sprite::Player));
+        render::de_bg();
+        render::pixel(
+            theme.glyph(Sprite::Player),
+            self.player.x * 2 + x,
+            self.player.y + y,
+        );
+        render::de_fg();
     }
 
     fn draw_inventory_side(&self, x: u32, mut y: u32) {
-        cod::color::de();
-        let x = x + (WIDTH as u32 * 2) + 2;
+        render::de();
+        for slot in Slot::ALL {
+            if let Some(item) = self.player.equipped(slot) {
+                render::blit(format!("{}: {}", slot.name(), item.name), x, y);
+                y += 1;
+            }
+        }
+
         for item in &self.player.inventory {
-            cod::blit(&item.name, x, y);
+            if item.count > 1 {
+                render::blit(format!("{} x{}", item.name, item.count), x, y);
+            } else {
+                render::blit(&item.name, x, y);
+            }
             y += 1;
         }
     }
 
-    /// Draws the full inventory screen.
-    ///
-    /// Returns the y coordinate of each item name.
-    pub fn draw_inventory_full(&self) -> Vec<u32> {
-        let mut coords = Vec::with_capacity(self.player.inventory.len());
-        cod::clear::all();
+    /// Draws the full inventory screen, with a marker next to the
+    /// `selected` row. The equipment slots are the first rows, followed
+    /// by everything carried.
+    pub fn draw_inventory_full(&self, selected: usize) {
+        render::clear();
+
+        render::fg(8);
+        render::blit("Equipped", 2, 1);
+        let mut y = 2;
+        let mut row = 0;
+        for slot in Slot::ALL {
+            if row == selected {
+                render::de();
+                render::pixel('-', 1, y);
+            }
+            row += 1;
+
+            render::de();
+            render::blit(format!("{}:", slot.name()), 3, y);
+            match self.player.equipped(slot) {
+                Some(item) => y += item.draw(12, y),
+                None => {
+                    render::fg(8);
+                    render::blit("nothing", 12, y);
+                    y += 1;
+                }
+            }
+        }
+
+        render::fg(8);
+        render::blit(
+            format!("Carried ({}/{CARRY_LIMIT})", self.player.inventory.len()),
+            2,
+            y + 1,
+        );
+        y += 2;
+        for (i, item) in self.player.inventory.iter().enumerate() {
+            if row == selected {
+                render::de();
+                render::pixel('-', 1, y);
+                if item.slot.is_some() {
+                    self.draw_equip_diff(i, 44, 2);
+                }
+            }
+            row += 1;
 
-        let mut y = 1;
-        for item in &self.player.inventory {
-            coords.push(y);
             y += item.draw(3, y) + 1;
         }
-        y -= 2;
 
-        cod::color::de_fg();
-        cod::pixel('+', 0, 0);
-        if y != 0 {
-            cod::orth_line('|', 0, 1, 0, y).unwrap();
+        render::de();
+        render::pixel('+', 0, 0);
+        render::orth_line('|', 0, 1, 0, y);
+        render::pixel('+', 0, y);
+        render::blit("- Inventory -+", 1, 0);
+
+        render::fg(8);
+        render::blit(
+            "Enter: use/equip  e: equip/unequip  u: use  d: drop  q: back",
+            2,
+            y + 2,
+        );
+        render::flush();
+    }
+
+    /// Draws how the player's stats would change if they equipped the
+    /// inventory item at `i`.
+    fn draw_equip_diff(&self, i: usize, x: u32, mut y: u32) {
+        let mut after = self.player.clone();
+        if !after.equip(i) {
+            return;
         }
-        cod::pixel('+', 0, y);
-        cod::blit("- Inventory -+", 1, 0);
 
-        cod::goto::bot();
-        cod::flush();
+        render::fg(8);
+        render::blit("If equipped:", x, y);
+        y += 1;
+
+        let mut changed = false;
+        for stat in Stat::ALL {
+            let (old, new) = (self.player.stat(stat), after.stat(stat));
+            if old == new {
+                continue;
+            }
+            changed = true;
+
+            render::goto(x + 1, y);
+            render::fg(stat.color());
+            render::print(format!("{}: {old} -> {new} ", stat.name()));
+            render::fg(if new > old { 2 } else { 1 });
+            render::print(format!("({:+})", new as i64 - old as i64));
+            y += 1;
+        }
 
-        coords
+        if !changed {
+            render::fg(8);
+            render::blit("no change", x + 1, y);
+        }
     }
 
     fn draw_key(&self) {
-        cod::goto::pos(0, HEIGHT as u32 + 1);
+        let theme = theme::get();
+
+        render::goto(0, HEIGHT as u32 + 1);
         for kind in [
             TileKind::Water,
             TileKind::Grass,
@@ -279,61 +778,103 @@ impl World {
             TileKind::Hill,
             TileKind::Mountain,
         ] {
-            let (r, g, b) = kind.color();
-            cod::color::tc_fg(r, g, b);
-            let (r, g, b) = kind.dark_faded_color();
-            cod::color::tc_bg(r, g, b);
-            print!(" {kind:?}: {} ", kind as u8 as char);
-            cod::goto::right(1);
+            render::fg(kind.color());
+            render::bg(kind.dark_faded_color());
+            render::print(format!(" {kind:?}: {} ", theme.tile_char(kind)));
+            render::right(1);
         }
 
-        cod::color::de();
-
-        cod::color::fg(108);
-        print!("\nFood: +  ");
-
-        cod::color::fg(210);
-        print!("Enemy: !  ");
-
-        cod::color::fg(136);
-        cod::color::bg(9);
-        print!("Boss: #");
-        cod::color::de();
-        print!("  ");
+        render::de();
+
+        render::fg(theme.color(Sprite::Food));
+        render::print(format!("\nFood: {}  ", theme.glyph(Sprite::Food)));
+
+        render::fg(theme.color(Sprite::Enemy));
+        render::print(format!("Enemy: {}  ", theme.glyph(Sprite::Enemy)));
+
+        render::fg(theme.color(Sprite::Boss));
+        render::bg(9);
+        render::print(format!("Boss: {}", theme.glyph(Sprite::Boss)));
+        render::de();
+        render::print("  ");
+
+        // only the kinds of enemy that are around right now
+        for archetype in self.bestiary.archetypes() {
+            if self
+                .entities
+                .iter()
+                .any(|e| e.kind.archetype(&self.bestiary) == Some(archetype))
+            {
+                let look = theme.enemy(&archetype.id);
+                render::fg(look.color.unwrap_or(archetype.color.into()));
+                render::print(format!(
+                    "{}: {}  ",
+                    archetype.name,
+                    look.glyph.unwrap_or(archetype.sprite)
+                ));
+            }
+        }
+        render::de();
 
-        cod::color::fg(140);
-        print!("\nPlayer: G  ");
+        render::fg(theme.color(Sprite::Player));
+        render::print(format!("\nPlayer: {}  ", theme.glyph(Sprite::Player)));
 
         if self.player.health <= 4 {
-            cod::color::fg(0);
-            cod::color::bg(1);
+            render::fg(0);
+            render::bg(1);
         } else {
-            cod::color::fg(1);
+            render::fg(1);
         }
-        print!("Health: {:2}", self.player.health);
-        cod::color::de_bg();
-        cod::goto::right(2);
+        render::print(format!("Health: {:2}", self.player.health));
+        render::de_bg();
+        render::right(2);
 
-        cod::color::fg(7);
-        print!("Damage: {:2}  ", self.player.damage);
+        render::fg(7);
+        render::print(format!("Damage: {:2}  ", self.player.damage()));
 
-        let food = self.player.hunger_cap.saturating_sub(self.player.hunger);
+        let food = self
+            .player
+            .stat(Stat::HungerCap)
+            .saturating_sub(self.player.hunger);
         if food <= 3 {
-            cod::color::fg(1);
+            render::fg(1);
         } else {
-            cod::color::fg(223);
+            render::fg(223);
         }
 
-        print!("Hunger: {:2}  ", food);
+        render::print(format!("Hunger: {:2}  ", food));
 
-        let water = self.player.thirst_cap.saturating_sub(self.player.thirst);
+        let water = self
+            .player
+            .stat(Stat::ThirstCap)
+            .saturating_sub(self.player.thirst);
         if water <= 1 {
-            cod::color::fg(1);
+            render::fg(1);
         } else {
-            cod::color::fg(12);
+            render::fg(12);
         }
 
-        print!("Thirst: {:2}", water);
+        render::print(format!("Thirst: {:2}", water));
+
+        for status in &self.player.statuses {
+            render::fg(status.kind.color());
+            render::print(format!("  {} ({})", status.kind.name(), status.turns));
+        }
+
+        // the nearest boss, if there are several
+        let boss = self
+            .entities
+            .iter()
+            .filter_map(|e| match &e.kind {
+                EntityKind::Boss { health, state, .. } => Some((e, *health, state)),
+                _ => None,
+            })
+            .min_by_key(|(e, ..)| e.x.abs_diff(self.player.x).max(e.y.abs_diff(self.player.y)));
+        if let Some((_, health, state)) = boss {
+            render::goto(0, HEIGHT as u32 + 4);
+            boss::draw_health(health, state, 20);
+        }
+        render::de();
     }
 
     #[allow(dead_code)]
@@ -350,47 +891,67 @@ impl World {
     }
 
     pub fn go(&mut self, direction: Direction) -> TurnResult {
-        let (diff_x, diff_y) = direction.diff();
+        if direction.is_diagonal() && !self.diagonal {
+            return good!(InvalidMove, direction);
+        }
 
-        let x = self.player.x.saturating_add_signed(diff_x);
-        let y = self.player.y.saturating_add_signed(diff_y);
+        let (diff_x, diff_y) = direction.diff();
 
-        if (x == self.player.x && y == self.player.y) || x as usize >= WIDTH || y as usize >= HEIGHT
-        {
-            let neighbor = self.map.sector().neighbor(direction);
+        let x = self
+            .player
+            .x
+            .checked_add_signed(diff_x)
+            .filter(|x| (*x as usize) < WIDTH);
+        let y = self
+            .player
+            .y
+            .checked_add_signed(diff_y)
+            .filter(|y| (*y as usize) < HEIGHT);
+
+        let (Some(x), Some(y)) = (x, y) else {
+            // only the edges actually crossed count, so moving diagonally
+            // along an edge goes to the side neighbor, and only crossing
+            // a corner goes to the corner neighbor
+            let crossed_x = if x.is_none() { diff_x } else { 0 };
+            let crossed_y = if y.is_none() { diff_y } else { 0 };
+            let crossed = Direction::from_diff(crossed_x, crossed_y).unwrap();
+
+            let neighbor = self.map.sector().neighbor(crossed).map(str::to_string);
             if let Some(new_sector) = neighbor {
                 let entities = std::mem::take(&mut self.entities);
-                self.map.save_entities(
-                    self.map.sector().id,
-                    entities.into_iter().filter(|e| e.persist).collect(),
-                );
-                self.entities = self.map.load(new_sector);
+                let id = self.map.sector().id.clone();
+                self.map
+                    .save_entities(&id, entities.into_iter().filter(|e| e.persist).collect());
+                self.entities = self.map.load(&new_sector);
 
                 if let Some((x, y)) = self.map.sector().return_tile {
                     self.player.x = x;
                     self.player.y = y;
                 } else {
-                    match direction {
-                        Direction::Up => self.player.y = HEIGHT as u32 - 1,
-                        Direction::Down => self.player.y = 0,
-                        Direction::Left => self.player.x = WIDTH as u32 - 1,
-                        Direction::Right => self.player.x = 0,
-                    }
+                    self.player.x = match (x, crossed_x) {
+                        (Some(x), _) => x,
+                        (None, -1) => WIDTH as u32 - 1,
+                        (None, _) => 0,
+                    };
+                    self.player.y = match (y, crossed_y) {
+                        (Some(y), _) => y,
+                        (None, -1) => HEIGHT as u32 - 1,
+                        (None, _) => 0,
+                    };
                 }
 
                 return good!();
             } else {
                 return good!(InvalidMove, direction);
             }
-        }
+        };
 
-        if let Some(new_sector) = self.map.sector().get_entrance(x, y) {
+        if let Some(new_sector) = self.map.sector().get_entrance(x, y).map(str::to_string) {
             let entities = std::mem::take(&mut self.entities);
-            self.map.save_entities(
-                self.map.current_sector.id,
-                entities.into_iter().filter(|e| e.persist).collect(),
-            );
-            self.entities = self.map.load(new_sector);
+            let id = self.map.sector().id.clone();
+            self.map
+                .save_entities(&id, entities.into_iter().filter(|e| e.persist).collect());
+            self.entities = self.map.load(&new_sector);
 
             self.player.x = 0;
             self.player.y = 0;
@@ -398,7 +959,7 @@ impl World {
             if !self.map.sector().do_survival {
                 self.player.thirst = 0;
                 self.player.hunger = 0;
-                self.player.health = self.player.max_health;
+                self.player.health = self.player.max_health();
             }
 
             good!()
@@ -417,12 +978,17 @@ impl World {
                     let mut res = good!();
 
                     let mut kill = Vec::new();
+                    let mut killed = Vec::new();
+                    let mut fought = None;
                     for (i, entity) in self.entities.iter_mut().enumerate() {
                         if entity.x == x && entity.y == y {
-                            res = entity.interact(&mut self.player, &mut self.map)?;
+                            res =
+                                entity.interact(&mut self.player, &mut self.map, &mut self.rng)?;
+                            fought = Some(entity.clone());
 
                             if !entity.alive {
                                 kill.push(i);
+                                killed.push(entity.clone());
                             }
                         }
                     }
@@ -431,9 +997,208 @@ impl World {
                         self.despawn(e - i);
                     }
 
+                    for enemy in killed {
+                        res = self.loot(res, &enemy);
+                    }
+
+                    if let Some(enemy) = fought {
+                        self.inflict(&res, &enemy);
+                    }
+
                     Ok(res)
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::Ai;
+    use crate::item::{Buff, Item};
+    use crate::world_map;
+
+    /// A world in the built-in map with nothing else in the player's
+    /// sector. The player starts at (12, 14), with water below them and
+    /// grass above.
+    fn empty_world() -> World {
+        let mut world = World::new(world_map::builtin().unwrap(), 1);
+        world.entities.clear();
+        world
+    }
+
+    fn troll(health: u32) -> EntityKind {
+        EntityKind::Enemy {
+            health,
+            damage: 1,
+            ai: Ai::default(),
+            archetype: Some("troll".to_string()),
+        }
+    }
+
+    fn enemy(health: u32, damage: u32) -> EntityKind {
+        EntityKind::Enemy {
+            health,
+            damage,
+            ai: Ai::default(),
+            archetype: None,
+        }
+    }
+
+    #[test]
+    fn water_blocks_movement() {
+        let mut world = empty_world();
+        world.player.thirst = 3;
+
+        let events = world.update(Action::Move(Direction::Down)).unwrap();
+        assert_eq!(events, vec![GoodResult::WaterMove]);
+        assert_eq!((world.player.x, world.player.y), (12, 14));
+        assert_eq!(world.player.thirst, 0);
+        assert_eq!(world.turn, 0);
+    }
+
+    #[test]
+    fn moving_takes_a_turn() {
+        let mut world = empty_world();
+
+        world.update(Action::Move(Direction::Up)).unwrap();
+        assert_eq!((world.player.x, world.player.y), (12, 13));
+        assert_eq!(world.turn, 1);
+    }
+
+    #[test]
+    fn fights_trade_blows() {
+        let mut world = empty_world();
+        world.spawn(12, 13, enemy(3, 2));
+
+        let events = world.update(Action::Move(Direction::Up)).unwrap();
+        assert_eq!(events, vec![GoodResult::Fight(2, 2)]);
+        assert_eq!(world.player.health, 8);
+        assert_eq!(world.entities.len(), 1);
+    }
+
+    #[test]
+    fn weak_enemies_die_in_one_hit() {
+        let mut world = empty_world();
+        world.spawn(12, 13, enemy(1, 5));
+
+        let events = world.update(Action::Move(Direction::Up)).unwrap();
+        assert!(matches!(events[..], [GoodResult::WonFight(..)]));
+        assert_eq!(world.player.health, 10);
+        assert!(world.entities.is_empty());
+    }
+
+    #[test]
+    fn equipment_comes_off_cleanly() {
+        let mut world = empty_world();
+        world.player.inventory.push(Item {
+            name: "Troll Hide".to_string(),
+            buffs: vec![Buff::Armor(3)],
+            debuffs: vec![Buff::Speed(10)],
+            slot: Some(Slot::Armor),
+            ..Default::default()
+        });
+        let before = world.player.clone();
+
+        let events = world.update(Action::Equip(0)).unwrap();
+        assert_eq!(events, vec![GoodResult::Equipped("Troll Hide".to_string())]);
+        assert_eq!(world.player.stat(Stat::Armor), 3);
+
+        world.update(Action::Unequip(Slot::Armor)).unwrap();
+        for stat in Stat::ALL {
+            assert_eq!(world.player.stat(stat), before.stat(stat), "{stat:?}");
+        }
+    }
+
+    #[test]
+    fn lethal_hits_end_the_run() {
+        let mut world = empty_world();
+        world.spawn(12, 13, enemy(5, 10));
+
+        assert_eq!(
+            world.update(Action::Move(Direction::Up)),
+            Err(BadResult::ViolentDeath)
+        );
+    }
+    #[test]
+    fn diagonal_moves_need_eight_way_movement() {
+        let mut world = empty_world();
+
+        let events = world.update(Action::Move(Direction::UpLeft)).unwrap();
+        assert_eq!(events, vec![GoodResult::InvalidMove(Direction::UpLeft)]);
+        assert_eq!((world.player.x, world.player.y), (12, 14));
+
+        world.diagonal = true;
+        world.update(Action::Move(Direction::UpLeft)).unwrap();
+        assert_eq!((world.player.x, world.player.y), (11, 13));
+        assert_eq!(world.turn, 1);
+    }
+
+    #[test]
+    fn diagonal_moves_cross_into_the_right_neighbor() {
+        let mut world = empty_world();
+        world.diagonal = true;
+
+        // only the left edge is crossed, so this goes left
+        world.player.x = 0;
+        world.player.y = 5;
+        world.update(Action::Move(Direction::DownLeft)).unwrap();
+        assert_eq!(world.map.sector().id, "plains1");
+        assert_eq!((world.player.x, world.player.y), (WIDTH as u32 - 1, 6));
+
+        // across the corner, to the corner neighbor
+        let mut world = empty_world();
+        world.diagonal = true;
+        world.player.x = 0;
+        world.player.y = HEIGHT as u32 - 1;
+        world.update(Action::Move(Direction::DownLeft)).unwrap();
+        assert_eq!(world.map.sector().id, "plains3");
+        assert_eq!((world.player.x, world.player.y), (WIDTH as u32 - 1, 0));
+    }
+    #[test]
+    fn loot_chances_scale_with_difficulty() {
+        let table = empty_world().bestiary.get("troll").unwrap().loot.len();
+
+        let mut world = empty_world();
+        world.difficulty = Difficulty::normal().loot_mul(0.0);
+        world.spawn(12, 13, troll(1));
+        let events = world.update(Action::Move(Direction::Up)).unwrap();
+        assert!(matches!(&events[..], [GoodResult::WonFight(_, names)] if names.is_empty()));
+        assert!(world.entities.is_empty());
+
+        // every chance is certain once it's scaled up enough
+        let mut world = empty_world();
+        world.difficulty = Difficulty::normal().loot_mul(100.0);
+        world.spawn(12, 13, troll(1));
+        let events = world.update(Action::Move(Direction::Up)).unwrap();
+        assert!(matches!(&events[..], [GoodResult::WonFight(_, names)] if names.len() == table));
+        assert_eq!(world.entities.len(), table);
+        assert!(world.entities.iter().all(|e| (e.x, e.y) == (12, 13)));
+    }
+    #[test]
+    fn dropped_items_can_be_picked_up_again() {
+        let mut world = empty_world();
+        let amulet = Item::basic("Amulet", 42, Buff::Vision(2));
+        world.spawn(12, 13, EntityKind::Item(amulet.clone()));
+        let despawned = |world: &World| world.despawned.iter().filter(|(_, id)| *id == 42).count();
+
+        world.update(Action::Move(Direction::Up)).unwrap();
+        assert_eq!(world.player.inventory, vec![amulet.clone()]);
+        assert_eq!(despawned(&world), 1);
+
+        let events = world.update(Action::Drop(0)).unwrap();
+        assert_eq!(events, vec![GoodResult::Dropped("Amulet".to_string())]);
+        assert!(world.player.inventory.is_empty());
+        assert_eq!(despawned(&world), 0);
+        assert!(world
+            .entities
+            .iter()
+            .any(|e| (e.x, e.y) == (12, 13) && e.kind == EntityKind::Item(amulet.clone())));
+
+        world.update(Action::Interact).unwrap();
+        assert_eq!(world.player.inventory, vec![amulet]);
+        assert_eq!(despawned(&world), 1);
+        assert!(world.entities.is_empty());
+    }
+}
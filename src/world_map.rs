@@ -1,194 +1,806 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::Path;
 
+use serde::Deserialize;
+
+use crate::bestiary::{Archetype, Bestiary};
+use crate::boss::{Attack, Phase};
 use crate::difficulty::Difficulty;
 use crate::entity::{Entity, EntityKind};
-use crate::item::{Buff, Item};
-use crate::map::{Direction, Tile, TileKind};
+use crate::map::{Direction, TileKind, HEIGHT, WIDTH};
 use crate::sector::Sector;
 
-macro_rules! sector {
-    ( $sectors:expr, $id:expr => $neighbors:expr, $entities:expr $(,)? ) => {
-        $sectors.insert(
-            $id,
-            Sector::new(
-                include_str!(concat!("../map/", $id, ".txt")),
-                $id,
-                $entities,
-                $neighbors,
+const BUILTIN_MANIFEST: &str = include_str!("../map/world.json");
+const BUILTIN_FILES: &[(&str, &str)] = &[
+    ("enemies.json", include_str!("../map/enemies.json")),
+    ("start.txt", include_str!("../map/start.txt")),
+    ("plains1.txt", include_str!("../map/plains1.txt")),
+    ("plains2.txt", include_str!("../map/plains2.txt")),
+    ("plains3.txt", include_str!("../map/plains3.txt")),
+    ("plains4.txt", include_str!("../map/plains4.txt")),
+    ("mountains1.txt", include_str!("../map/mountains1.txt")),
+    ("peninsula1.txt", include_str!("../map/peninsula1.txt")),
+    ("village1.txt", include_str!("../map/village1.txt")),
+];
+
+/// A fully loaded and validated world, ready to be handed to `Map::new`.
+#[derive(Debug, Clone)]
+pub struct WorldDef {
+    pub sectors: HashMap<String, Sector>,
+    pub start: String,
+    pub spawn: (u32, u32),
+    pub bestiary: Bestiary,
+}
+
+#[derive(Debug)]
+pub enum WorldError {
+    Io(String, std::io::Error),
+    Parse(serde_json::Error),
+    MissingEnemies(String),
+    Enemies(String, serde_json::Error),
+    DuplicateArchetype(String),
+    InvalidArchetype {
+        id: String,
+        reason: &'static str,
+    },
+    UnknownArchetype {
+        sector: String,
+        id: String,
+    },
+    UnknownStart(String),
+    SpawnOutOfBounds(u32, u32),
+    DuplicateSector(String),
+    MissingMap {
+        sector: String,
+        map: String,
+    },
+    UnknownDifficulty {
+        sector: String,
+        name: String,
+    },
+    InvalidTile {
+        sector: String,
+        tile: char,
+    },
+    WrongSize {
+        sector: String,
+        tiles: usize,
+    },
+    UnknownNeighbor {
+        sector: String,
+        neighbor: String,
+    },
+    UnknownEntrance {
+        sector: String,
+        target: String,
+    },
+    OutOfBounds {
+        sector: String,
+        what: &'static str,
+        x: u32,
+        y: u32,
+    },
+    DuplicateId {
+        sector: String,
+        id: u32,
+    },
+    /// An NPC was given no dialogue.
+    EmptyDialogue {
+        sector: String,
+        id: u32,
+    },
+    /// An NPC's dialogue starts past its last line.
+    DialogueOutOfRange {
+        sector: String,
+        id: u32,
+        idx: usize,
+        len: usize,
+    },
+    /// An NPC hands over an item on a line of dialogue it doesn't have.
+    GiftOutOfRange {
+        sector: String,
+        id: u32,
+        item: String,
+        idx: usize,
+        len: usize,
+    },
+    /// An enemy or boss would be dead before the player meets it.
+    NoHealth {
+        sector: String,
+        x: u32,
+        y: u32,
+    },
+    InvalidPhases {
+        sector: String,
+        id: u32,
+        reason: &'static str,
+    },
+    /// A boss would block a tile off the edge of the map when it dies.
+    BlockOutOfBounds {
+        sector: String,
+        id: u32,
+    },
+}
+
+impl Display for WorldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "failed to read `{path}`: {e}"),
+            Self::Parse(e) => write!(f, "invalid world manifest: {e}"),
+            Self::MissingEnemies(file) => write!(f, "enemy catalog `{file}` not found"),
+            Self::Enemies(file, e) => write!(f, "invalid enemy catalog `{file}`: {e}"),
+            Self::DuplicateArchetype(id) => write!(f, "enemy `{id}` is defined twice"),
+            Self::InvalidArchetype { id, reason } => write!(f, "enemy `{id}`: {reason}"),
+            Self::UnknownArchetype { sector, id } => {
+                write!(f, "sector `{sector}`: enemy type `{id}` does not exist")
+            }
+            Self::UnknownStart(id) => write!(f, "start sector `{id}` does not exist"),
+            Self::SpawnOutOfBounds(x, y) => write!(f, "spawn ({x}, {y}) is out of bounds"),
+            Self::DuplicateSector(id) => write!(f, "sector `{id}` is defined twice"),
+            Self::MissingMap { sector, map } => {
+                write!(f, "sector `{sector}`: map `{map}` not found")
+            }
+            Self::UnknownDifficulty { sector, name } => {
+                write!(f, "sector `{sector}`: unknown difficulty `{name}`")
+            }
+            Self::InvalidTile { sector, tile } => {
+                write!(f, "sector `{sector}`: invalid tile `{tile}`")
+            }
+            Self::WrongSize { sector, tiles } => write!(
+                f,
+                "sector `{sector}`: map has {tiles} tiles, expected {WIDTH}x{HEIGHT}"
             ),
-        )
-    };
+            Self::UnknownNeighbor { sector, neighbor } => {
+                write!(f, "sector `{sector}`: neighbor `{neighbor}` does not exist")
+            }
+            Self::UnknownEntrance { sector, target } => {
+                write!(f, "sector `{sector}`: entrance leads to unknown sector `{target}`")
+            }
+            Self::OutOfBounds { sector, what, x, y } => {
+                write!(f, "sector `{sector}`: {what} at ({x}, {y}) is out of bounds")
+            }
+            Self::DuplicateId { sector, id } => {
+                write!(f, "sector `{sector}`: entity id {id} is already in use")
+            }
+            Self::EmptyDialogue { sector, id } => {
+                write!(f, "sector `{sector}`: NPC {id} has no dialogue")
+            }
+            Self::DialogueOutOfRange {
+                sector,
+                id,
+                idx,
+                len,
+            } => write!(
+                f,
+                "sector `{sector}`: NPC {id} starts at line {idx} but only has {len} lines of dialogue"
+            ),
+            Self::GiftOutOfRange {
+                sector,
+                id,
+                item,
+                idx,
+                len,
+            } => write!(
+                f,
+                "sector `{sector}`: NPC {id} gives `{item}` on line {idx} but only has {len} lines of dialogue"
+            ),
+            Self::NoHealth { sector, x, y } => {
+                write!(f, "sector `{sector}`: enemy at ({x}, {y}) has no health")
+            }
+            Self::InvalidPhases { sector, id, reason } => {
+                write!(f, "sector `{sector}`: boss {id}: {reason}")
+            }
+            Self::BlockOutOfBounds { sector, id } => {
+                write!(f, "sector `{sector}`: boss {id} blocks a tile off the map")
+            }
+        }
+    }
+}
 
-    ( $sectors:expr, $id:expr => $neighbors:expr, $entities:expr, $difficulty:expr $(,)? ) => {
-        $sectors.insert(
-            $id,
-            Sector::new(
-                include_str!(concat!("../map/", $id, ".txt")),
-                $id,
-                $entities,
-                $neighbors,
-            )
-            .with_difficulty($difficulty),
-        )
-    };
+impl std::error::Error for WorldError {}
+
+#[derive(Deserialize)]
+struct Manifest {
+    start: String,
+    spawn: (u32, u32),
+    /// The file the enemy catalog is read from, if the world has one.
+    #[serde(default)]
+    enemies: Option<String>,
+    sectors: Vec<SectorDef>,
+}
+
+#[derive(Deserialize)]
+struct SectorDef {
+    id: String,
+    map: String,
+    #[serde(default)]
+    neighbors: Neighbors,
+    #[serde(default)]
+    entrances: Vec<EntranceDef>,
+    #[serde(default)]
+    return_tile: Option<(u32, u32)>,
+    #[serde(default)]
+    difficulty: DifficultyDef,
+    #[serde(default = "default_true")]
+    do_survival: bool,
+    #[serde(default)]
+    entities: Vec<EntityDef>,
+}
+
+#[derive(Default, Deserialize)]
+struct Neighbors {
+    up: Option<String>,
+    down: Option<String>,
+    left: Option<String>,
+    right: Option<String>,
+    up_left: Option<String>,
+    up_right: Option<String>,
+    down_left: Option<String>,
+    down_right: Option<String>,
+}
+
+impl Neighbors {
+    fn iter(&self) -> impl Iterator<Item = &String> {
+        [
+            &self.up,
+            &self.down,
+            &self.left,
+            &self.right,
+            &self.up_left,
+            &self.up_right,
+            &self.down_left,
+            &self.down_right,
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    fn into_array(self) -> [Option<String>; 8] {
+        let mut neighbors: [Option<String>; 8] = Default::default();
+        neighbors[Direction::Up as usize] = self.up;
+        neighbors[Direction::Down as usize] = self.down;
+        neighbors[Direction::Left as usize] = self.left;
+        neighbors[Direction::Right as usize] = self.right;
+        neighbors[Direction::UpLeft as usize] = self.up_left;
+        neighbors[Direction::UpRight as usize] = self.up_right;
+        neighbors[Direction::DownLeft as usize] = self.down_left;
+        neighbors[Direction::DownRight as usize] = self.down_right;
+        neighbors
+    }
+}
+
+#[derive(Deserialize)]
+struct EntranceDef {
+    x: u32,
+    y: u32,
+    sector: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DifficultyDef {
+    Preset(String),
+    Custom(Difficulty),
 }
 
-pub fn sectors() -> HashMap<&'static str, Sector> {
-    let mut entity_id_counter = 0;
-    let mut entity_id = move || {
-        entity_id_counter += 1;
-        entity_id_counter
+impl Default for DifficultyDef {
+    fn default() -> Self {
+        Self::Preset("normal".to_string())
+    }
+}
+
+#[derive(Deserialize)]
+struct EntityDef {
+    x: u32,
+    y: u32,
+    kind: EntityKind,
+    #[serde(default = "default_true")]
+    persist: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Loads the world bundled into the binary.
+pub fn builtin() -> Result<WorldDef, WorldError> {
+    parse(BUILTIN_MANIFEST, |name| {
+        BUILTIN_FILES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, file)| file.to_string())
+    })
+}
+
+/// Loads a world manifest from disk. Map files and the enemy catalog
+/// are resolved relative to the manifest's directory.
+pub fn load(path: impl AsRef<Path>) -> Result<WorldDef, WorldError> {
+    let path = path.as_ref();
+    let manifest =
+        fs::read_to_string(path).map_err(|e| WorldError::Io(path.display().to_string(), e))?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+
+    parse(&manifest, |name| fs::read_to_string(dir.join(name)).ok())
+}
+
+fn parse(
+    manifest: &str,
+    mut read_file: impl FnMut(&str) -> Option<String>,
+) -> Result<WorldDef, WorldError> {
+    let manifest: Manifest = serde_json::from_str(manifest).map_err(WorldError::Parse)?;
+
+    let bestiary = match &manifest.enemies {
+        Some(file) => {
+            let Some(json) = read_file(file) else {
+                return Err(WorldError::MissingEnemies(file.clone()));
+            };
+            parse_bestiary(file, &json)?
+        }
+        None => Bestiary::default(),
     };
 
+    let ids: HashSet<&str> = manifest.sectors.iter().map(|s| s.id.as_str()).collect();
+    let mut entity_ids = HashSet::new();
     let mut sectors = HashMap::new();
 
-    sector!(
-        sectors, "start" =>
-        [None, None, Some("plains1"), None],
-        vec![
-            Entity::new(
-                7,
-                7,
-                EntityKind::Boss {
-                    health: 15,
-                    damage: 3,
-                    damage_gain: 2,
-                    id: entity_id(),
-                    block: (
-                        Direction::Up,
-                        Tile {
-                            kind: TileKind::Grass,
-                        },
-                    ),
-                },
-                true,
-            ),
-            Entity::new(
-                16,
-                12,
-                EntityKind::Boss {
-                    health: 30,
-                    damage: 5,
-                    damage_gain: 2,
-                    id: entity_id(),
-                    block: (
-                        Direction::Right,
-                        Tile {
-                            kind: TileKind::Forest,
-                        },
-                    ),
-                },
-                true,
-            ),
-        ],
-        Difficulty::easy(),
-    );
-
-    sector!(sectors, "plains1" =>
-        [Some("plains4"), Some("plains3"), Some("plains2"), Some("start")],
-        vec![],
-    );
-
-    sector!(sectors, "plains2" =>
-        [None, None, Some("mountains1"), Some("plains1")],
-        vec![],
-    );
-
-    sector!(sectors, "plains3" =>
-        [Some("plains1"), Some("peninsula1"), None, None],
-        vec![
-            Entity::new(4, 11,
-                EntityKind::Item(Item::basic("Sword", entity_id(), Buff::Damage(2))),
-                true,
-            ),
-        ],
-    );
-
-    sector!(sectors, "plains4" =>
-        [None, Some("plains1"), None, None],
-        vec![
-            Entity::new(20, 11,
-                EntityKind::Item(Item::basic("Chestplate", entity_id(), Buff::MaxHealth(3))),
-                true,
-            ),
-        ],
-    );
-
-    sectors.get_mut("plains4").unwrap().entrance(17, 11, "village1");
-
-    sector!(sectors, "mountains1" =>
-        [None, None, None, Some("plains2")],
-        vec![
-            Entity::new(3, 6,
-                EntityKind::Item(Item::buffs("Vial of Fortitude", entity_id(), vec![
-                        Buff::MaxHealth(3),
-                        Buff::HungerCap(2),
-                        Buff::ThirstCap(2),
-                ])),
-                true,
-            ),
-            Entity::new(20, 7,
-                EntityKind::Npc {
-                    dialogue: &[
-                        "You'd better be careful,\nthese wilds are dangerous.",
-                        "They say there's valuable\ntreasure past the river.",
-                        "Well, good luck, traveler!"
-                    ],
-                    dialogue_idx: Some(0),
-                    items: vec![],
-                    id: entity_id(),
-                },
-                true,
-            ),
-        ],
-        Difficulty {
-            food_mul: 0.5,
-            food_food_mul: 1.5,
-
-            enemy_mul: 2.0,
-            enemy_health_mul: 2.0,
-            enemy_damage_mul: 2.0,
-        },
-    );
-
-    sector!(sectors, "peninsula1" =>
-        [Some("plains3"), None, None, None],
-        vec![
-            Entity::new(17, 14,
-                EntityKind::Item(Item::full("Battleaxe", entity_id(), vec![
-                    Buff::Damage(3),
-                    Buff::MaxHealth(2),
-                ], vec![
-                    Buff::HungerCap(2),
-                    Buff::ThirstCap(2),
-                ])),
-                true,
-            ),
-        ],
-        Difficulty::hard()
-    );
-
-    sectors.insert("village1", Sector::town(
-        include_str!("../map/village1.txt"),
-        "village1",
-        vec![
-            Entity::new(3, 2,
-                EntityKind::Npc {
-                    dialogue: &[
-                        "Welcome, traveler!\nStay as long as you like.",
-                        "We're a quiet town, so don't\nexpect many attractions.",
-                        "I've got to get back to work."
-                    ],
-                    dialogue_idx: Some(0),
-                    items: vec![(Item::basic("Pouch", entity_id(), Buff::HungerCap(2)), 2)],
-                    id: entity_id(),
-                },
-                true,
-            ),
-        ],
-        "plains4",
-        17, 11,
-    ));
+    for def in &manifest.sectors {
+        validate(def, &ids, &mut entity_ids, &bestiary)?;
+    }
+
+    for def in manifest.sectors {
+        let Some(map) = read_file(&def.map) else {
+            return Err(WorldError::MissingMap {
+                sector: def.id,
+                map: def.map,
+            });
+        };
+        validate_map(&def.id, &map)?;
+
+        let difficulty = match def.difficulty {
+            DifficultyDef::Preset(name) => match Difficulty::from_name(&name) {
+                Some(difficulty) => difficulty,
+                None => {
+                    return Err(WorldError::UnknownDifficulty {
+                        sector: def.id,
+                        name,
+                    })
+                }
+            },
+            DifficultyDef::Custom(difficulty) => difficulty,
+        };
+
+        let entities = def
+            .entities
+            .into_iter()
+            .map(|mut e| {
+                if let EntityKind::Boss { health, state, .. } = &mut e.kind {
+                    state.max_health = *health;
+                }
+                Entity::new(e.x, e.y, e.kind, e.persist)
+            })
+            .collect();
+
+        let mut sector = Sector::new(&map, def.id.clone(), entities, def.neighbors.into_array())
+            .with_difficulty(difficulty);
+        sector.do_survival = def.do_survival;
+        sector.return_tile = def.return_tile;
+        for entrance in def.entrances {
+            sector.entrance(entrance.x, entrance.y, entrance.sector);
+        }
+
+        if sectors.insert(def.id.clone(), sector).is_some() {
+            return Err(WorldError::DuplicateSector(def.id));
+        }
+    }
+
+    if !sectors.contains_key(&manifest.start) {
+        return Err(WorldError::UnknownStart(manifest.start));
+    }
+
+    let (x, y) = manifest.spawn;
+    if !in_bounds(x, y) {
+        return Err(WorldError::SpawnOutOfBounds(x, y));
+    }
+
+    Ok(WorldDef {
+        sectors,
+        start: manifest.start,
+        spawn: manifest.spawn,
+        bestiary,
+    })
+}
+
+fn parse_bestiary(file: &str, json: &str) -> Result<Bestiary, WorldError> {
+    let archetypes: Vec<Archetype> =
+        serde_json::from_str(json).map_err(|e| WorldError::Enemies(file.to_string(), e))?;
+
+    let mut ids = HashSet::new();
+    for archetype in &archetypes {
+        let invalid = |reason| WorldError::InvalidArchetype {
+            id: archetype.id.clone(),
+            reason,
+        };
+
+        if !ids.insert(archetype.id.as_str()) {
+            return Err(WorldError::DuplicateArchetype(archetype.id.clone()));
+        }
+        if archetype.health.0 > archetype.health.1 {
+            return Err(invalid("health range is backwards"));
+        }
+        if archetype.health.0 == 0 {
+            return Err(invalid("health must be at least 1"));
+        }
+        if archetype.damage.0 > archetype.damage.1 {
+            return Err(invalid("damage range is backwards"));
+        }
+        if archetype.pack.0 > archetype.pack.1 || archetype.pack.0 == 0 {
+            return Err(invalid("pack size must be at least 1"));
+        }
+    }
+
+    Ok(Bestiary::new(archetypes))
+}
+
+fn validate(
+    def: &SectorDef,
+    ids: &HashSet<&str>,
+    entity_ids: &mut HashSet<u32>,
+    bestiary: &Bestiary,
+) -> Result<(), WorldError> {
+    let sector = || def.id.clone();
+
+    for neighbor in def.neighbors.iter() {
+        if !ids.contains(neighbor.as_str()) {
+            return Err(WorldError::UnknownNeighbor {
+                sector: sector(),
+                neighbor: neighbor.clone(),
+            });
+        }
+    }
+
+    for entrance in &def.entrances {
+        if !ids.contains(entrance.sector.as_str()) {
+            return Err(WorldError::UnknownEntrance {
+                sector: sector(),
+                target: entrance.sector.clone(),
+            });
+        }
+        if !in_bounds(entrance.x, entrance.y) {
+            return Err(WorldError::OutOfBounds {
+                sector: sector(),
+                what: "entrance",
+                x: entrance.x,
+                y: entrance.y,
+            });
+        }
+    }
+
+    if let Some((x, y)) = def.return_tile {
+        if !in_bounds(x, y) {
+            return Err(WorldError::OutOfBounds {
+                sector: sector(),
+                what: "return tile",
+                x,
+                y,
+            });
+        }
+    }
+
+    for entity in &def.entities {
+        if !in_bounds(entity.x, entity.y) {
+            return Err(WorldError::OutOfBounds {
+                sector: sector(),
+                what: "entity",
+                x: entity.x,
+                y: entity.y,
+            });
+        }
+
+        if let EntityKind::Enemy {
+            archetype: Some(id),
+            ..
+        } = &entity.kind
+        {
+            if bestiary.get(id).is_none() {
+                return Err(WorldError::UnknownArchetype {
+                    sector: sector(),
+                    id: id.clone(),
+                });
+            }
+        }
+
+        if let EntityKind::Enemy { health: 0, .. } | EntityKind::Boss { health: 0, .. } =
+            entity.kind
+        {
+            return Err(WorldError::NoHealth {
+                sector: sector(),
+                x: entity.x,
+                y: entity.y,
+            });
+        }
+
+        match &entity.kind {
+            EntityKind::Npc {
+                dialogue,
+                dialogue_idx,
+                items,
+                id,
+            } => {
+                if dialogue.is_empty() {
+                    return Err(WorldError::EmptyDialogue {
+                        sector: sector(),
+                        id: *id,
+                    });
+                }
+                if let Some(idx) = *dialogue_idx {
+                    if idx >= dialogue.len() {
+                        return Err(WorldError::DialogueOutOfRange {
+                            sector: sector(),
+                            id: *id,
+                            idx,
+                            len: dialogue.len(),
+                        });
+                    }
+                }
+                for (item, idx) in items {
+                    if *idx >= dialogue.len() {
+                        return Err(WorldError::GiftOutOfRange {
+                            sector: sector(),
+                            id: *id,
+                            item: item.name.clone(),
+                            idx: *idx,
+                            len: dialogue.len(),
+                        });
+                    }
+                }
+            }
+            EntityKind::Boss {
+                health,
+                id,
+                phases,
+                block: (dir, _),
+                ..
+            } => {
+                if let Err(reason) = validate_phases(*health, phases) {
+                    return Err(WorldError::InvalidPhases {
+                        sector: sector(),
+                        id: *id,
+                        reason,
+                    });
+                }
+
+                let (dx, dy) = dir.diff();
+                let target = entity
+                    .x
+                    .checked_add_signed(dx)
+                    .zip(entity.y.checked_add_signed(dy));
+                if !target.is_some_and(|(x, y)| in_bounds(x, y)) {
+                    return Err(WorldError::BlockOutOfBounds {
+                        sector: sector(),
+                        id: *id,
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        let ids = match &entity.kind {
+            EntityKind::Npc { id, items, .. } => {
+                let mut ids = vec![*id];
+                ids.extend(items.iter().map(|(item, _)| item.id).filter(|id| *id != 0));
+                ids
+            }
+            EntityKind::Boss { id, .. } => vec![*id],
+            EntityKind::Item(item) if item.id != 0 => vec![item.id],
+            _ => Vec::new(),
+        };
+
+        for id in ids {
+            if !entity_ids.insert(id) {
+                return Err(WorldError::DuplicateId {
+                    sector: sector(),
+                    id,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that each phase starts while the boss is still alive, after
+/// the one before it, and that its attack and minions can do something.
+fn validate_phases(health: u32, phases: &[Phase]) -> Result<(), &'static str> {
+    let mut last = health;
+    for phase in phases {
+        if phase.below == 0 {
+            return Err("a phase can't start below 0 health");
+        }
+        if phase.below >= last {
+            return Err("phases must start at decreasing health, below the boss's");
+        }
+        last = phase.below;
+
+        match phase.attack {
+            Some(Attack::Line { length: 0, .. }) => return Err("line attacks need a length"),
+            Some(Attack::Burst { radius: 0, .. }) => return Err("burst attacks need a radius"),
+            _ => {}
+        }
+        if phase
+            .summon
+            .as_ref()
+            .is_some_and(|s| s.count == 0 || s.health == 0)
+        {
+            return Err("summons need a count and health of at least 1");
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_map(sector: &str, map: &str) -> Result<(), WorldError> {
+    let mut tiles = 0;
+    for ch in map.chars().filter(|c| !c.is_whitespace()) {
+        if TileKind::from_char(ch).is_none() {
+            return Err(WorldError::InvalidTile {
+                sector: sector.to_string(),
+                tile: ch,
+            });
+        }
+        tiles += 1;
+    }
+
+    if tiles != WIDTH * HEIGHT {
+        return Err(WorldError::WrongSize {
+            sector: sector.to_string(),
+            tiles,
+        });
+    }
+
+    Ok(())
+}
+
+fn in_bounds(x: u32, y: u32) -> bool {
+    (x as usize) < WIDTH && (y as usize) < HEIGHT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a one-sector world made of the start map, with `entity`
+    /// in it.
+    fn with_entity(entity: &str) -> Result<WorldDef, WorldError> {
+        let manifest = format!(
+            r#"{{
+                "start": "start",
+                "spawn": [12, 14],
+                "sectors": [{{ "id": "start", "map": "start.txt", "entities": [{entity}] }}]
+            }}"#
+        );
+        parse(&manifest, |name| {
+            BUILTIN_FILES
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, file)| file.to_string())
+        })
+    }
+
+    fn npc(dialogue: &str, idx: &str) -> String {
+        npc_giving(dialogue, idx, "[]")
+    }
+
+    fn npc_giving(dialogue: &str, idx: &str, items: &str) -> String {
+        format!(
+            r#"{{ "x": 1, "y": 1, "kind": {{ "Npc": {{
+                "dialogue": {dialogue}, "dialogue_idx": {idx}, "items": {items}, "id": 1
+            }} }} }}"#
+        )
+    }
+
+    fn enemy(health: u32) -> String {
+        format!(
+            r#"{{ "x": 1, "y": 1, "kind": {{ "Enemy": {{ "health": {health}, "damage": 1 }} }} }}"#
+        )
+    }
+
+    fn boss(phases: &str) -> String {
+        boss_at(1, "Up", phases)
+    }
+
+    fn boss_at(y: u32, block: &str, phases: &str) -> String {
+        format!(
+            r#"{{ "x": 1, "y": {y}, "kind": {{ "Boss": {{
+                "health": 20, "damage": 3, "damage_gain": 2, "id": 1,
+                "block": ["{block}", {{ "kind": "Grass" }}],
+                "phases": {phases}
+            }} }} }}"#
+        )
+    }
+
+    #[test]
+    fn builtin_world_is_valid() {
+        builtin().unwrap();
+    }
+
+    #[test]
+    fn npc_dialogue_is_checked() {
+        assert!(with_entity(&npc(r#"["Hi"]"#, "0")).is_ok());
+        assert!(with_entity(&npc(r#"["Hi"]"#, "null")).is_ok());
+        assert!(matches!(
+            with_entity(&npc("[]", "null")),
+            Err(WorldError::EmptyDialogue { id: 1, .. })
+        ));
+        assert!(matches!(
+            with_entity(&npc(r#"["Hi", "Bye"]"#, "2")),
+            Err(WorldError::DialogueOutOfRange { idx: 2, len: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn npc_gifts_are_checked() {
+        let sword = r#"{ "name": "Sword", "id": 2 }"#;
+        assert!(with_entity(&npc_giving(
+            r#"["Hi", "Take this"]"#,
+            "0",
+            &format!("[[{sword}, 1]]")
+        ))
+        .is_ok());
+        assert!(matches!(
+            with_entity(&npc_giving(
+                r#"["Hi", "Take this"]"#,
+                "0",
+                &format!("[[{sword}, 2]]")
+            )),
+            Err(WorldError::GiftOutOfRange { idx: 2, len: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn enemies_need_health() {
+        assert!(with_entity(&enemy(1)).is_ok());
+        assert!(matches!(
+            with_entity(&enemy(0)),
+            Err(WorldError::NoHealth { x: 1, y: 1, .. })
+        ));
+        assert!(matches!(
+            with_entity(&boss_at(1, "Up", "[]").replace(r#""health": 20"#, r#""health": 0"#)),
+            Err(WorldError::NoHealth { .. })
+        ));
+    }
+
+    #[test]
+    fn boss_phases_are_checked() {
+        assert!(with_entity(&boss(r#"[{ "below": 15 }, { "below": 5 }]"#)).is_ok());
 
-    sectors
+        for phases in [
+            r#"[{ "below": 20 }]"#,
+            r#"[{ "below": 0 }]"#,
+            r#"[{ "below": 5 }, { "below": 15 }]"#,
+            r#"[{ "below": 10, "attack": { "Burst": { "radius": 0, "damage": 2 } } }]"#,
+            r#"[{ "below": 10, "summon": { "count": 2, "health": 0, "damage": 1 } }]"#,
+        ] {
+            assert!(
+                matches!(
+                    with_entity(&boss(phases)),
+                    Err(WorldError::InvalidPhases { .. })
+                ),
+                "{phases} should be rejected"
+            );
+        }
+    }
+    #[test]
+    fn boss_blocks_stay_on_the_map() {
+        assert!(with_entity(&boss_at(1, "Up", "[]")).is_ok());
+        assert!(matches!(
+            with_entity(&boss_at(0, "Up", "[]")),
+            Err(WorldError::BlockOutOfBounds { id: 1, .. })
+        ));
+        assert!(matches!(
+            with_entity(&boss_at(HEIGHT as u32 - 1, "Down", "[]")),
+            Err(WorldError::BlockOutOfBounds { .. })
+        ));
+    }
 }